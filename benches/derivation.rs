@@ -0,0 +1,76 @@
+//! Leaf grammar derived by `LSystem` and by `Derivation`, and in parallel
+//! with the `rayon` feature: `cargo bench --bench derivation --features rayon`.
+
+use plants::systems::{
+  derivation::Derivation,
+  leaf::{leaf_rule, leaf_rule_into, LeafParameters, LEAF_AXIOM},
+  LSystem,
+};
+use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng, SeedableRng};
+use std::{
+  hint::black_box,
+  time::{Duration, Instant},
+};
+
+const RUNS: u32 = 20;
+
+/// Mean time of `RUNS` runs, after one to warm up.
+fn time<R>(mut run: impl FnMut() -> R) -> Duration {
+  black_box(run());
+  let start = Instant::now();
+  for _ in 0..RUNS {
+    black_box(run());
+  }
+  start.elapsed() / RUNS
+}
+
+fn main() {
+  let parameters: LeafParameters = Standard.sample(&mut StdRng::seed_from_u64(0));
+  println!(
+    "{:>5} {:>9} {:>12} {:>12} {:>8}",
+    "steps", "symbols", "LSystem", "Derivation", "speedup"
+  );
+  for steps in [15, 20, 25, 30, 35] {
+    let l_system = || LSystem::new(LEAF_AXIOM.to_vec(), leaf_rule, parameters.clone());
+    let derivation = || Derivation::new(LEAF_AXIOM.to_vec(), leaf_rule_into, parameters.clone());
+    let symbols = l_system().nth(steps).unwrap().len();
+    assert_eq!(symbols, derivation().advance(steps).len());
+
+    let old = time(|| l_system().nth(steps).unwrap());
+    let new = time(|| derivation().advance(steps).len());
+    println!(
+      "{:>5} {:>9} {:>12?} {:>12?} {:>7.2}x",
+      steps,
+      symbols,
+      old,
+      new,
+      old.as_secs_f64() / new.as_secs_f64()
+    );
+  }
+  #[cfg(feature = "rayon")]
+  parallel(&parameters);
+}
+
+#[cfg(feature = "rayon")]
+fn parallel(parameters: &LeafParameters) {
+  println!();
+  println!(
+    "{:>5} {:>9} {:>12} {:>12} {:>8}",
+    "steps", "symbols", "step", "par_step", "speedup"
+  );
+  for steps in [100, 200, 300] {
+    let derivation = || Derivation::new(LEAF_AXIOM.to_vec(), leaf_rule_into, parameters.clone());
+    let symbols = derivation().advance(steps).len();
+
+    let sequential = time(|| derivation().advance(steps).len());
+    let parallel = time(|| derivation().par_advance(steps).len());
+    println!(
+      "{:>5} {:>9} {:>12?} {:>12?} {:>7.2}x",
+      steps,
+      symbols,
+      sequential,
+      parallel,
+      sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+  }
+}
@@ -1,20 +1,25 @@
 use nannou::{prelude::Key, App};
 use plants::utils::app::{make_static_artwork, Artwork, ArtworkOptions, BaseModel, StaticArtwork};
-use plants::utils::draw::BrushDrawer;
+use plants::utils::draw::{BrushDrawer, FillDrawer};
 use plants::utils::geometry::{ProjectionMatrix, WorldBox, WorldPoint, WorldTransform};
 use plants::{
   systems::{
-    leaf::{leaf_rule, LEAF_AXIOM},
-    LSystem,
+    control::{DeriveOptions, StopReason},
+    derivation::Derivation,
+    leaf::{leaf_rule_into, LEAF_AXIOM},
   },
   turtle,
 };
 use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng, SeedableRng};
-use std::{f64::consts::FRAC_PI_3, usize::MAX};
+use std::f64::consts::FRAC_PI_3;
+
+/// Longest string derived, however many steps are asked for.
+const MAX_LENGTH: usize = 1_000_000;
 
 struct Model {
   base_model: BaseModel,
   steps: usize,
+  filled: bool,
   turtle_params: turtle::polygon::Params,
 }
 
@@ -23,6 +28,7 @@ impl Artwork for Model {
     Self {
       base_model,
       steps: 20,
+      filled: false,
       turtle_params: turtle::polygon::Params::new(FRAC_PI_3),
     }
   }
@@ -43,7 +49,8 @@ impl Artwork for Model {
   fn key_pressed(&mut self, _app: &App, key: Key) {
     match key {
       Key::Equals => self.steps += 1,
-      Key::Minus => self.steps = (self.steps - 1).clamp(0, MAX),
+      Key::Minus => self.steps = (self.steps - 1).clamp(0, usize::MAX),
+      Key::F => self.filled = !self.filled,
       _ => {}
     }
   }
@@ -63,7 +70,13 @@ impl StaticArtwork for Model {
       WorldPoint::new(size / 2.0, size / 2.0, 0.0),
     );
 
-    let polygons_3d = grow_l_system(self.steps, &mut rng, &self.turtle_params, bbox);
+    let polygons_3d = grow_l_system(
+      self.steps,
+      self.base_model.seed,
+      &mut rng,
+      &self.turtle_params,
+      bbox,
+    );
     let projection = ProjectionMatrix::perspective(1.0);
 
     let polygons_2d = polygons_3d
@@ -78,6 +91,13 @@ impl StaticArtwork for Model {
       .collect::<Vec<_>>();
 
     polygons_2d.iter().for_each(|polygon| {
+      if self.filled {
+        draw
+          .mesh()
+          .fill_from_points(polygon)
+          .color(nannou::color::BLACK);
+        return;
+      }
       draw
         .polyline()
         .stroke_weight(2.0)
@@ -89,13 +109,20 @@ impl StaticArtwork for Model {
 
 fn grow_l_system(
   steps: usize,
+  seed: u64,
   rng: &mut StdRng,
   turtle_params: &turtle::polygon::Params,
   bbox_out: WorldBox,
 ) -> Vec<Vec<WorldPoint>> {
-  let mut l_system = LSystem::new(LEAF_AXIOM.to_vec(), leaf_rule, Standard.sample(rng));
-  let commands = l_system.nth(steps).unwrap();
-  let polygons = turtle::polygon::to_geom(commands, turtle_params);
+  let mut derivation =
+    Derivation::new(LEAF_AXIOM.to_vec(), leaf_rule_into, Standard.sample(rng)).with_seed(seed);
+  let outcome = derivation.derive(steps, DeriveOptions::new().with_max_length(MAX_LENGTH));
+  if outcome.reason != StopReason::Completed {
+    println!("stopped at step {}: {}", outcome.generation, outcome.reason);
+  }
+  let commands = derivation.into_current();
+  let polygons = turtle::polygon::to_geom(commands, turtle_params)
+    .expect("leaf grammar derived a malformed string");
   let bbox_in = WorldBox::from_points(polygons.iter().flatten());
 
   let transform = WorldTransform::translation(
@@ -106,7 +133,11 @@ fn grow_l_system(
   .then_scale(
     bbox_out.width() / bbox_in.width(),
     bbox_out.height() / bbox_in.height(),
-    bbox_out.depth() / bbox_in.depth(),
+    if bbox_in.depth() > 0.0 {
+      bbox_out.depth() / bbox_in.depth()
+    } else {
+      1.0
+    },
   )
   .then_scale(0.95, 0.95, 0.95);
 
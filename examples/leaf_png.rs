@@ -0,0 +1,74 @@
+//! Renders the leaf artwork to PNG on the CPU, without opening a window:
+//! `cargo run --example leaf_png -- [seed] [steps] [path]`.
+
+use plants::utils::geometry::{ProjectionMatrix, WorldBox, WorldPoint, WorldTransform};
+use plants::utils::{raster::Canvas, TEXTURE_SIZE};
+use plants::{
+  systems::{
+    derivation::Derivation,
+    leaf::{leaf_rule_into, LEAF_AXIOM},
+  },
+  turtle,
+};
+use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng, SeedableRng};
+use std::{env, f64::consts::FRAC_PI_3};
+
+fn main() {
+  let mut args = env::args().skip(1);
+  let seed = args.next().map_or(0, |seed| seed.parse().expect("seed"));
+  let steps = args
+    .next()
+    .map_or(20, |steps| steps.parse().expect("steps"));
+  let path = args.next().unwrap_or(format!("frame_{}.png", seed));
+
+  let mut rng = StdRng::seed_from_u64(seed);
+  let size = TEXTURE_SIZE;
+  let half = size[0] as f64 / 2.0;
+  let bbox = WorldBox::new(
+    WorldPoint::new(-half, -half, 0.0),
+    WorldPoint::new(half, half, 0.0),
+  );
+
+  let mut derivation = Derivation::new(
+    LEAF_AXIOM.to_vec(),
+    leaf_rule_into,
+    Standard.sample(&mut rng),
+  )
+  .with_seed(seed);
+  derivation.advance(steps);
+  let commands = derivation.into_current();
+  let polygons = turtle::polygon::to_geom(commands, &turtle::polygon::Params::new(FRAC_PI_3))
+    .expect("leaf grammar derived a malformed string");
+  let bbox_in = WorldBox::from_points(polygons.iter().flatten());
+  let transform = WorldTransform::translation(
+    -(bbox_in.min.x + bbox_in.max.x) / 2.0,
+    -(bbox_in.min.y + bbox_in.max.y) / 2.0,
+    0.0,
+  )
+  .then_scale(
+    bbox.width() / bbox_in.width(),
+    bbox.height() / bbox_in.height(),
+    1.0,
+  )
+  .then_scale(0.95, 0.95, 0.95);
+  let projection = ProjectionMatrix::perspective(1.0);
+
+  let outlines = polygons
+    .iter()
+    .map(|polygon| {
+      polygon
+        .iter()
+        .filter_map(|point| transform.transform_point3d(*point))
+        .filter_map(|point| projection.transform_point3d(point))
+        .map(|point| point.xy())
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+  let mut canvas = Canvas::new(size, [255, 255, 255]);
+  for outline in outlines.iter() {
+    canvas.brush(outline, 7.0, &mut rng, 2.0, [0, 0, 0]);
+  }
+  canvas.save_png(&path).unwrap();
+  println!("{}", path);
+}
@@ -0,0 +1,43 @@
+//! Prints how the leaf grammar grows, as a table or as JSON:
+//! `cargo run --example leaf_stats -- [seed] [steps] [potential_decrement] [--json]`.
+
+use plants::systems::{
+  analysis::{GrowthRecorder, Tally},
+  derivation::Derivation,
+  leaf::{leaf_rule_into, LeafParameters, LEAF_AXIOM},
+};
+use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng, SeedableRng};
+use std::env;
+
+fn main() {
+  let (flags, mut args): (Vec<_>, Vec<_>) =
+    env::args().skip(1).partition(|arg| arg.starts_with("--"));
+  args.reverse();
+  let seed = args.pop().map_or(0, |seed| seed.parse().expect("seed"));
+  let steps = args.pop().map_or(20, |steps| steps.parse().expect("steps"));
+  let mut parameters: LeafParameters = Standard.sample(&mut StdRng::seed_from_u64(seed));
+  if let Some(decrement) = args.pop() {
+    parameters =
+      parameters.with_potential_decrement(decrement.parse().expect("potential decrement"));
+  }
+
+  let tally = Tally::new();
+  let mut recorder = GrowthRecorder::new().with_tally(tally.clone());
+  let mut derivation = Derivation::new(
+    LEAF_AXIOM.to_vec(),
+    tally.track_sink(leaf_rule_into),
+    parameters,
+  )
+  .with_seed(seed);
+  recorder.record(derivation.current());
+  for _ in 0..steps {
+    recorder.record(derivation.step());
+  }
+
+  let report = recorder.into_report();
+  if flags.iter().any(|flag| flag == "--json") {
+    println!("{:#}", report.to_json());
+  } else {
+    print!("{}", report.to_table());
+  }
+}
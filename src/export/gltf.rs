@@ -0,0 +1,191 @@
+use super::{Organ, Palette};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+/// Binary glTF 2.0 buffer being filled, along with the views and accessors
+/// describing it.
+#[derive(Default)]
+struct Buffer {
+  data: Vec<u8>,
+  views: Vec<Value>,
+  accessors: Vec<Value>,
+}
+
+impl Buffer {
+  /// Adds a `VEC3` float attribute and returns its accessor.
+  fn vec3(&mut self, values: impl Iterator<Item = [f32; 3]>, bounds: bool) -> usize {
+    let start = self.data.len();
+    let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    let mut count = 0;
+    for value in values {
+      for axis in 0..3 {
+        min[axis] = min[axis].min(value[axis]);
+        max[axis] = max[axis].max(value[axis]);
+        self.data.extend(value[axis].to_le_bytes());
+      }
+      count += 1;
+    }
+    let mut accessor = json!({
+      "bufferView": self.view(start, ARRAY_BUFFER),
+      "componentType": FLOAT,
+      "count": count,
+      "type": "VEC3",
+    });
+    // Required for positions only.
+    if bounds {
+      accessor["min"] = json!(min);
+      accessor["max"] = json!(max);
+    }
+    self.accessors.push(accessor);
+    self.accessors.len() - 1
+  }
+
+  fn indices(&mut self, triangles: &[[u32; 3]]) -> usize {
+    let start = self.data.len();
+    for vertex in triangles.iter().flatten() {
+      self.data.extend(vertex.to_le_bytes());
+    }
+    let view = self.view(start, ELEMENT_ARRAY_BUFFER);
+    self.accessors.push(json!({
+      "bufferView": view,
+      "componentType": UNSIGNED_INT,
+      "count": triangles.len() * 3,
+      "type": "SCALAR",
+    }));
+    self.accessors.len() - 1
+  }
+
+  fn view(&mut self, start: usize, target: u32) -> usize {
+    self.views.push(json!({
+      "buffer": 0,
+      "byteOffset": start,
+      "byteLength": self.data.len() - start,
+      "target": target,
+    }));
+    self.views.len() - 1
+  }
+}
+
+fn write_chunk<W: Write>(
+  writer: &mut W,
+  kind: &[u8; 4],
+  data: &[u8],
+  padding: u8,
+) -> io::Result<()> {
+  let padded = data.len().next_multiple_of(4);
+  writer.write_all(&(padded as u32).to_le_bytes())?;
+  writer.write_all(kind)?;
+  writer.write_all(data)?;
+  writer.write_all(&vec![padding; padded - data.len()])
+}
+
+/// Binary glTF 2.0 (`.glb`), one node per organ. Organs without triangles
+/// are left out, and without any the scene is empty.
+pub fn write_glb<W: Write>(mut writer: W, organs: &[Organ], palette: &Palette) -> io::Result<()> {
+  let mut buffer = Buffer::default();
+  let mut meshes = vec![];
+  let mut nodes = vec![];
+  for organ in organs.iter().filter(|organ| !organ.mesh.is_empty()) {
+    let mesh = &organ.mesh;
+    let position = buffer.vec3(
+      mesh
+        .positions
+        .iter()
+        .map(|point| point.to_array().map(|value| value as f32)),
+      true,
+    );
+    let normal = buffer.vec3(
+      mesh
+        .normals
+        .iter()
+        .map(|normal| normal.to_array().map(|value| value as f32)),
+      false,
+    );
+    let color = buffer.vec3(mesh.colors.iter().map(|color| palette.color(*color)), false);
+    let indices = buffer.indices(&mesh.triangles);
+    meshes.push(json!({
+      "name": organ.name,
+      "primitives": [{
+        "attributes": { "POSITION": position, "NORMAL": normal, "COLOR_0": color },
+        "indices": indices,
+      }],
+    }));
+    nodes.push(json!({ "name": organ.name, "mesh": meshes.len() - 1 }));
+  }
+
+  let mut document = json!({
+    "asset": { "version": "2.0", "generator": env!("CARGO_PKG_NAME") },
+    "scene": 0,
+    "scenes": [{}],
+  });
+  // glTF forbids empty arrays.
+  if !nodes.is_empty() {
+    document["scenes"][0]["nodes"] = json!((0..nodes.len()).collect::<Vec<_>>());
+    document["nodes"] = json!(nodes);
+    document["meshes"] = json!(meshes);
+  }
+  if !buffer.data.is_empty() {
+    document["buffers"] = json!([{ "byteLength": buffer.data.len() }]);
+    document["bufferViews"] = json!(buffer.views);
+    document["accessors"] = json!(buffer.accessors);
+  }
+  let document = serde_json::to_vec(&document)?;
+
+  let json_length = document.len().next_multiple_of(4);
+  let binary_length = buffer.data.len().next_multiple_of(4);
+  let mut length = 12 + 8 + json_length;
+  if binary_length > 0 {
+    length += 8 + binary_length;
+  }
+  writer.write_all(b"glTF")?;
+  writer.write_all(&2u32.to_le_bytes())?;
+  writer.write_all(&(length as u32).to_le_bytes())?;
+  write_chunk(&mut writer, b"JSON", &document, b' ')?;
+  if binary_length > 0 {
+    write_chunk(&mut writer, b"BIN\0", &buffer.data, 0)?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::geometry::{Mesh, WorldPoint, WorldVector};
+
+  /// JSON chunk of a GLB file.
+  fn document(organs: &[Organ]) -> Value {
+    let mut glb = vec![];
+    write_glb(&mut glb, organs, &Palette::default()).unwrap();
+    let length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+    assert_eq!(&glb[16..20], b"JSON");
+    serde_json::from_slice(&glb[20..20 + length]).unwrap()
+  }
+
+  #[test]
+  fn empty_scenes_have_no_empty_arrays() {
+    let document = document(&[Organ::new("empty", Mesh::new())]);
+    assert_eq!(document["scenes"], json!([{}]));
+    for key in ["nodes", "meshes", "buffers", "bufferViews", "accessors"] {
+      assert!(document.get(key).is_none(), "{} is present", key);
+    }
+  }
+
+  #[test]
+  fn organs_are_nodes() {
+    let mut mesh = Mesh::new();
+    let normal = WorldVector::new(0.0, 0.0, 1.0);
+    let a = mesh.push_vertex(WorldPoint::new(0.0, 0.0, 0.0), normal, 0);
+    let b = mesh.push_vertex(WorldPoint::new(1.0, 0.0, 0.0), normal, 0);
+    let c = mesh.push_vertex(WorldPoint::new(0.0, 1.0, 0.0), normal, 0);
+    mesh.push_triangle(a, b, c);
+    let document = document(&[Organ::new("blade", mesh), Organ::new("empty", Mesh::new())]);
+    assert_eq!(document["scenes"], json!([{ "nodes": [0] }]));
+    assert_eq!(document["nodes"], json!([{ "name": "blade", "mesh": 0 }]));
+    assert_eq!(document["meshes"].as_array().unwrap().len(), 1);
+  }
+}
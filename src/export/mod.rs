@@ -0,0 +1,83 @@
+//! Plant geometry written out for other tools. Every mesh format takes a list
+//! of organs, each turned into an object or a node when the format has them;
+//! drawings go to SVG or to a pen plotter.
+
+mod gltf;
+mod obj;
+mod plotter;
+mod ply;
+mod svg;
+
+use crate::{turtle::polygon::AttributedPolygon, utils::geometry::Mesh};
+
+pub use gltf::write_glb;
+pub use obj::write_obj;
+pub use plotter::{write_gcode, write_hpgl, Plot, PlotLayer, PlotStats, PlotterSettings};
+pub use ply::write_ply;
+pub use svg::{SvgDocument, SvgLayer};
+
+/// Part of a plant exported on its own, such as a stem or a leaf blade.
+#[derive(Clone, Debug)]
+pub struct Organ {
+  pub name: String,
+  pub mesh: Mesh,
+}
+
+impl Organ {
+  pub fn new(name: impl Into<String>, mesh: Mesh) -> Self {
+    Self {
+      name: name.into(),
+      mesh,
+    }
+  }
+}
+
+/// One organ per polygon, named after its index and colored by the turtle.
+pub fn polygon_organs<P>(polygons: &[AttributedPolygon<P>]) -> Vec<Organ> {
+  polygons
+    .iter()
+    .enumerate()
+    .map(|(index, polygon)| {
+      let mut mesh = Mesh::new();
+      mesh.push_polygon(
+        &polygon
+          .points
+          .iter()
+          .zip(polygon.attributes.iter())
+          .map(|(point, attributes)| (*point, attributes.color))
+          .collect::<Vec<_>>(),
+      );
+      Organ::new(format!("polygon.{}", index), mesh)
+    })
+    .collect()
+}
+
+const DEFAULT_COLORS: [[f32; 3]; 4] = [
+  [0.13, 0.37, 0.08],
+  [0.30, 0.55, 0.12],
+  [0.35, 0.22, 0.10],
+  [0.80, 0.70, 0.20],
+];
+
+/// Linear RGB colors the turtle's color index picks from, wrapping around.
+#[derive(Clone, Debug)]
+pub struct Palette {
+  colors: Vec<[f32; 3]>,
+}
+
+impl Palette {
+  pub fn new(colors: Vec<[f32; 3]>) -> Self {
+    assert!(!colors.is_empty(), "a palette needs at least one color");
+    Self { colors }
+  }
+
+  pub fn color(&self, index: usize) -> [f32; 3] {
+    self.colors[index % self.colors.len()]
+  }
+}
+
+impl Default for Palette {
+  fn default() -> Self {
+    Self::new(DEFAULT_COLORS.to_vec())
+  }
+}
@@ -0,0 +1,29 @@
+use super::{Organ, Palette};
+use std::io::{self, Write};
+
+/// Wavefront OBJ, one object per organ. Vertex colors follow the positions,
+/// as Blender and MeshLab read them.
+pub fn write_obj<W: Write>(mut writer: W, organs: &[Organ], palette: &Palette) -> io::Result<()> {
+  let mut offset = 1;
+  for organ in organs {
+    let mesh = &organ.mesh;
+    writeln!(writer, "o {}", organ.name)?;
+    for (position, color) in mesh.positions.iter().zip(mesh.colors.iter()) {
+      let [r, g, b] = palette.color(*color);
+      writeln!(
+        writer,
+        "v {} {} {} {} {} {}",
+        position.x, position.y, position.z, r, g, b
+      )?;
+    }
+    for normal in mesh.normals.iter() {
+      writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+    for triangle in mesh.triangles.iter() {
+      let [a, b, c] = triangle.map(|vertex| vertex as usize + offset);
+      writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+    offset += mesh.positions.len();
+  }
+  Ok(())
+}
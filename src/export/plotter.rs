@@ -0,0 +1,340 @@
+use crate::utils::geometry::ScreenPoint;
+use std::io::{self, Write};
+
+/// Improvement below which 2-opt stops reversing runs of paths.
+const EPSILON: f64 = 1e-9;
+
+fn distance(a: ScreenPoint, b: ScreenPoint) -> f64 {
+  (a - b).length()
+}
+
+fn start(path: &[ScreenPoint]) -> ScreenPoint {
+  path[0]
+}
+
+fn end(path: &[ScreenPoint]) -> ScreenPoint {
+  path[path.len() - 1]
+}
+
+/// Paths drawn with the same pen, numbered from 1 as HP-GL does.
+pub struct PlotLayer {
+  pub name: String,
+  pub pen: usize,
+  paths: Vec<Vec<ScreenPoint>>,
+}
+
+impl PlotLayer {
+  /// Paths in the order they're drawn, none of them empty.
+  pub fn paths(&self) -> &[Vec<ScreenPoint>] {
+    &self.paths
+  }
+
+  /// Adds a path drawn in one go; a single point makes a dot.
+  pub fn path(&mut self, points: &[ScreenPoint]) -> &mut Self {
+    if !points.is_empty() {
+      self.paths.push(points.to_vec());
+    }
+    self
+  }
+}
+
+/// Distances covered by the pen, in plot units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlotStats {
+  /// Pen down.
+  pub drawing: f64,
+  /// Pen up, from home and back, as both writers move.
+  pub travel: f64,
+  pub paths: usize,
+}
+
+/// Polylines to send to a pen plotter, layer after layer. Points are in plot
+/// units, millimetres from the machine's home unless it says otherwise;
+/// mapping drawing coordinates onto the paper is left to the caller.
+#[derive(Default)]
+pub struct Plot {
+  pub layers: Vec<PlotLayer>,
+}
+
+impl Plot {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Layer called `name`, drawn with `pen`, added after the others the first
+  /// time.
+  pub fn layer(&mut self, name: &str, pen: usize) -> &mut PlotLayer {
+    let index = match self.layers.iter().position(|layer| layer.name == name) {
+      Some(index) => index,
+      None => {
+        self.layers.push(PlotLayer {
+          name: name.to_string(),
+          pen,
+          paths: vec![],
+        });
+        self.layers.len() - 1
+      }
+    };
+    &mut self.layers[index]
+  }
+
+  /// Cuts down pen-up travel within each layer: paths whose ends lie within
+  /// `tolerance` of each other are merged, then the rest are ordered and
+  /// reversed greedily from where the pen is, and improved with 2-opt.
+  pub fn optimize(&mut self, tolerance: f64) {
+    let mut position = ScreenPoint::origin();
+    for layer in self.layers.iter_mut() {
+      let paths = merge(std::mem::take(&mut layer.paths), tolerance);
+      layer.paths = nearest_neighbour(paths, position);
+      two_opt(&mut layer.paths, position);
+      if let Some(path) = layer.paths.last() {
+        position = end(path);
+      }
+    }
+  }
+
+  pub fn stats(&self) -> PlotStats {
+    let mut stats = PlotStats::default();
+    let mut position = ScreenPoint::origin();
+    for path in self.layers.iter().flat_map(|layer| layer.paths.iter()) {
+      stats.travel += distance(position, start(path));
+      stats.drawing += path
+        .windows(2)
+        .map(|pair| distance(pair[0], pair[1]))
+        .sum::<f64>();
+      stats.paths += 1;
+      position = end(path);
+    }
+    stats.travel += distance(position, ScreenPoint::origin());
+    stats
+  }
+}
+
+/// Joins paths end to end, reversing them as needed, as long as some end
+/// touches another.
+fn merge(mut paths: Vec<Vec<ScreenPoint>>, tolerance: f64) -> Vec<Vec<ScreenPoint>> {
+  let touches = |a: ScreenPoint, b: ScreenPoint| distance(a, b) <= tolerance;
+  let mut merged = vec![];
+  while let Some(mut path) = paths.pop() {
+    loop {
+      let (first, last) = (start(&path), end(&path));
+      if let Some(index) = paths
+        .iter()
+        .position(|other| touches(last, start(other)) || touches(last, end(other)))
+      {
+        let mut other = paths.swap_remove(index);
+        if !touches(last, start(&other)) {
+          other.reverse();
+        }
+        path.extend(other.into_iter().skip(1));
+      } else if let Some(index) = paths
+        .iter()
+        .position(|other| touches(first, end(other)) || touches(first, start(other)))
+      {
+        let mut other = paths.swap_remove(index);
+        if !touches(first, end(&other)) {
+          other.reverse();
+        }
+        other.extend(path.into_iter().skip(1));
+        path = other;
+      } else {
+        break;
+      }
+    }
+    merged.push(path);
+  }
+  merged
+}
+
+/// Orders paths by always drawing next the one with the closest end,
+/// reversing it when that end is its last point.
+fn nearest_neighbour(
+  mut paths: Vec<Vec<ScreenPoint>>,
+  mut position: ScreenPoint,
+) -> Vec<Vec<ScreenPoint>> {
+  let mut ordered = Vec::with_capacity(paths.len());
+  while !paths.is_empty() {
+    let (index, reverse, _) = paths
+      .iter()
+      .enumerate()
+      .flat_map(|(index, path)| {
+        [
+          (index, false, distance(position, start(path))),
+          (index, true, distance(position, end(path))),
+        ]
+      })
+      .min_by(|a, b| a.2.total_cmp(&b.2))
+      .unwrap();
+    let mut path = paths.swap_remove(index);
+    if reverse {
+      path.reverse();
+    }
+    position = end(&path);
+    ordered.push(path);
+  }
+  ordered
+}
+
+/// Reverses runs of paths, each of them drawn backwards, for as long as that
+/// shortens the travel between them.
+fn two_opt(paths: &mut [Vec<ScreenPoint>], home: ScreenPoint) {
+  let mut improved = true;
+  while improved {
+    improved = false;
+    for i in 0..paths.len() {
+      for j in i + 1..paths.len() {
+        let before = if i == 0 { home } else { end(&paths[i - 1]) };
+        let after = paths.get(j + 1).map(|path| start(path));
+        let current = distance(before, start(&paths[i]))
+          + after.map_or(0.0, |after| distance(end(&paths[j]), after));
+        let reversed = distance(before, end(&paths[j]))
+          + after.map_or(0.0, |after| distance(start(&paths[i]), after));
+        if reversed < current - EPSILON {
+          paths[i..=j].reverse();
+          paths[i..=j].iter_mut().for_each(|path| path.reverse());
+          improved = true;
+        }
+      }
+    }
+  }
+}
+
+/// How the machine moves and lifts its pen.
+#[derive(Clone, Debug)]
+pub struct PlotterSettings {
+  /// Speed with the pen down, in units per minute.
+  pub draw_rate: f64,
+  /// Speed with the pen up, in units per minute. G-code only, HPGL plotters
+  /// travel as fast as they can.
+  pub travel_rate: f64,
+  /// G-code lifting the pen.
+  pub pen_up: String,
+  /// G-code lowering the pen.
+  pub pen_down: String,
+}
+
+impl Default for PlotterSettings {
+  fn default() -> Self {
+    Self {
+      draw_rate: 1500.0,
+      travel_rate: 3000.0,
+      pen_up: "G0 Z2".to_string(),
+      pen_down: "G1 Z0 F500".to_string(),
+    }
+  }
+}
+
+/// HP-GL plotter units per millimetre.
+const HPGL_UNITS: f64 = 40.0;
+
+fn hpgl_point(point: ScreenPoint) -> String {
+  format!(
+    "{},{}",
+    (point.x * HPGL_UNITS).round() as i64,
+    (point.y * HPGL_UNITS).round() as i64
+  )
+}
+
+/// HP-GL, with points in millimetres and a pen selected per layer, ending
+/// back home.
+pub fn write_hpgl<W: Write>(
+  mut writer: W,
+  plot: &Plot,
+  settings: &PlotterSettings,
+) -> io::Result<()> {
+  // Velocity is in centimetres per second.
+  writeln!(writer, "IN;VS{:.1};", settings.draw_rate / 600.0)?;
+  for layer in plot.layers.iter() {
+    writeln!(writer, "SP{};", layer.pen)?;
+    for path in layer.paths.iter() {
+      let mut points = path.iter().skip(1).map(|point| hpgl_point(*point));
+      let first = points.next().unwrap_or(hpgl_point(path[0]));
+      write!(writer, "PU{};PD{}", hpgl_point(path[0]), first)?;
+      for point in points {
+        write!(writer, ",{}", point)?;
+      }
+      writeln!(writer, ";")?;
+    }
+  }
+  writeln!(writer, "PU0,0;SP0;")
+}
+
+/// G-code in absolute millimetres, pausing with `M0` for the pen to be
+/// changed whenever a layer needs another one, ending back home.
+pub fn write_gcode<W: Write>(
+  mut writer: W,
+  plot: &Plot,
+  settings: &PlotterSettings,
+) -> io::Result<()> {
+  writeln!(writer, "G21\nG90\n{}", settings.pen_up)?;
+  let mut pen = None;
+  for layer in plot.layers.iter() {
+    writeln!(writer, "; layer {}", layer.name)?;
+    if pen.is_some_and(|pen| pen != layer.pen) {
+      writeln!(writer, "M0 ; pen {}", layer.pen)?;
+    }
+    pen = Some(layer.pen);
+    for path in layer.paths.iter() {
+      writeln!(
+        writer,
+        "G1 X{:.3} Y{:.3} F{}\n{}",
+        path[0].x, path[0].y, settings.travel_rate, settings.pen_down
+      )?;
+      for point in path.iter().skip(1) {
+        writeln!(
+          writer,
+          "G1 X{:.3} Y{:.3} F{}",
+          point.x, point.y, settings.draw_rate
+        )?;
+      }
+      writeln!(writer, "{}", settings.pen_up)?;
+    }
+  }
+  writeln!(writer, "G1 X0 Y0 F{}", settings.travel_rate)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn plot() -> Plot {
+    let mut plot = Plot::new();
+    plot
+      .layer("outline", 1)
+      .path(&[ScreenPoint::new(3.0, 4.0), ScreenPoint::new(3.0, 10.0)])
+      .path(&[])
+      .path(&[ScreenPoint::new(6.0, 14.0)]);
+    plot
+  }
+
+  #[test]
+  fn travel_includes_the_way_home() {
+    let stats = plot().stats();
+    assert_eq!(stats.paths, 2);
+    assert_eq!(stats.drawing, 6.0);
+    // Home to (3, 4), (3, 10) to (6, 14), then back home.
+    assert!((stats.travel - (5.0 + 5.0 + 232f64.sqrt())).abs() < 1e-9);
+  }
+
+  #[test]
+  fn writers_end_back_home() {
+    let settings = PlotterSettings::default();
+    let mut hpgl = vec![];
+    write_hpgl(&mut hpgl, &plot(), &settings).unwrap();
+    assert!(String::from_utf8(hpgl).unwrap().ends_with("PU0,0;SP0;\n"));
+    let mut gcode = vec![];
+    write_gcode(&mut gcode, &plot(), &settings).unwrap();
+    assert!(String::from_utf8(gcode)
+      .unwrap()
+      .ends_with("G1 X0 Y0 F3000\n"));
+  }
+
+  #[test]
+  fn empty_paths_are_dropped() {
+    let mut plot = plot();
+    plot.optimize(0.1);
+    assert!(plot.layers[0].paths().iter().all(|path| !path.is_empty()));
+    let mut hpgl = vec![];
+    write_hpgl(&mut hpgl, &plot, &PlotterSettings::default()).unwrap();
+  }
+}
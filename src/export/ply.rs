@@ -0,0 +1,55 @@
+use super::{Organ, Palette};
+use crate::utils::geometry::Mesh;
+use std::io::{self, Write};
+
+/// Binary little-endian PLY. The format has no notion of objects, so organs
+/// are merged into a single mesh.
+pub fn write_ply<W: Write>(mut writer: W, organs: &[Organ], palette: &Palette) -> io::Result<()> {
+  let mut mesh = Mesh::new();
+  for organ in organs {
+    mesh.append(&organ.mesh);
+  }
+  write!(
+    writer,
+    "ply\n\
+     format binary_little_endian 1.0\n\
+     element vertex {}\n\
+     property float x\n\
+     property float y\n\
+     property float z\n\
+     property float nx\n\
+     property float ny\n\
+     property float nz\n\
+     property uchar red\n\
+     property uchar green\n\
+     property uchar blue\n\
+     element face {}\n\
+     property list uchar uint vertex_indices\n\
+     end_header\n",
+    mesh.positions.len(),
+    mesh.triangles.len()
+  )?;
+  for ((position, normal), color) in mesh
+    .positions
+    .iter()
+    .zip(mesh.normals.iter())
+    .zip(mesh.colors.iter())
+  {
+    for value in [
+      position.x, position.y, position.z, normal.x, normal.y, normal.z,
+    ] {
+      writer.write_all(&(value as f32).to_le_bytes())?;
+    }
+    let color = palette
+      .color(*color)
+      .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    writer.write_all(&color)?;
+  }
+  for triangle in mesh.triangles.iter() {
+    writer.write_all(&[3])?;
+    for vertex in triangle {
+      writer.write_all(&vertex.to_le_bytes())?;
+    }
+  }
+  Ok(())
+}
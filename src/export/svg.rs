@@ -0,0 +1,151 @@
+use crate::utils::geometry::{brush_points, ScreenPoint};
+use rand::rngs::StdRng;
+use std::{
+  fmt::Write as _,
+  io::{self, Write},
+};
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn color([r, g, b]: [u8; 3]) -> String {
+  format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Points in drawing coordinates, centered with y going up, written in SVG
+/// user units where y goes down.
+fn points_attribute(points: &[ScreenPoint]) -> String {
+  let mut attribute = String::new();
+  for (index, point) in points.iter().enumerate() {
+    if index > 0 {
+      attribute.push(' ');
+    }
+    write!(attribute, "{:.3},{:.3}", point.x, -point.y).unwrap();
+  }
+  attribute
+}
+
+/// Group of elements written together, and shown as a layer by Inkscape.
+pub struct SvgLayer {
+  name: String,
+  elements: Vec<String>,
+}
+
+impl SvgLayer {
+  pub fn polyline(
+    &mut self,
+    points: &[ScreenPoint],
+    stroke_weight: f64,
+    stroke: [u8; 3],
+  ) -> &mut Self {
+    if points.len() > 1 {
+      self.elements.push(format!(
+        r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}"/>"#,
+        points_attribute(points),
+        color(stroke),
+        stroke_weight
+      ));
+    }
+    self
+  }
+
+  /// Filled with the nonzero rule, self-intersecting outlines included.
+  pub fn polygon(&mut self, points: &[ScreenPoint], fill: [u8; 3]) -> &mut Self {
+    if points.len() > 2 {
+      self.elements.push(format!(
+        r#"<polygon points="{}" fill="{}" stroke="none"/>"#,
+        points_attribute(points),
+        color(fill)
+      ));
+    }
+    self
+  }
+
+  /// Same scattered polyline as `BrushDrawer::brush_from_points`, given the
+  /// same random generator.
+  pub fn brush(
+    &mut self,
+    points: &[ScreenPoint],
+    radius: f64,
+    rng: &mut StdRng,
+    stroke_weight: f64,
+    stroke: [u8; 3],
+  ) -> &mut Self {
+    self.polyline(&brush_points(points, radius, rng), stroke_weight, stroke)
+  }
+}
+
+/// Vector counterpart of a drawn artwork, written without any window or GPU.
+/// Coordinates are those of nannou's `Draw`: the origin at the center of the
+/// texture and y going up.
+pub struct SvgDocument {
+  size: [u32; 2],
+  background: Option<[u8; 3]>,
+  layers: Vec<SvgLayer>,
+}
+
+impl SvgDocument {
+  /// Document whose viewBox covers a texture of `size`, such as
+  /// `utils::TEXTURE_SIZE`.
+  pub fn new(size: [u32; 2]) -> Self {
+    Self {
+      size,
+      background: None,
+      layers: vec![],
+    }
+  }
+
+  pub fn with_background(mut self, background: [u8; 3]) -> Self {
+    self.background = Some(background);
+    self
+  }
+
+  /// Layer called `name`, added on top of the others the first time.
+  pub fn layer(&mut self, name: &str) -> &mut SvgLayer {
+    let index = match self.layers.iter().position(|layer| layer.name == name) {
+      Some(index) => index,
+      None => {
+        self.layers.push(SvgLayer {
+          name: name.to_string(),
+          elements: vec![],
+        });
+        self.layers.len() - 1
+      }
+    };
+    &mut self.layers[index]
+  }
+
+  pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+    let [width, height] = self.size;
+    let (x, y) = (-(width as f64) / 2.0, -(height as f64) / 2.0);
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+      writer,
+      r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{width}" height="{height}" viewBox="{x} {y} {width} {height}">"#
+    )?;
+    if let Some(background) = self.background {
+      writeln!(
+        writer,
+        r#"<rect x="{x}" y="{y}" width="{width}" height="{height}" fill="{}"/>"#,
+        color(background)
+      )?;
+    }
+    for layer in self.layers.iter() {
+      let name = escape(&layer.name);
+      writeln!(
+        writer,
+        r#"<g inkscape:groupmode="layer" inkscape:label="{name}">"#
+      )?;
+      for element in layer.elements.iter() {
+        writeln!(writer, "{}", element)?;
+      }
+      writeln!(writer, "</g>")?;
+    }
+    writeln!(writer, "</svg>")
+  }
+}
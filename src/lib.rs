@@ -1,3 +1,5 @@
+#[cfg(feature = "export")]
+pub mod export;
 pub mod systems;
 pub mod turtle;
 pub mod utils;
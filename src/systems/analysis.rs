@@ -0,0 +1,441 @@
+//! Statistics on how a grammar grows, step after step.
+
+use super::{context::Context, derivation::Sink};
+use rand::rngs::StdRng;
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fmt::Display,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+};
+
+/// Symbols sorted into kinds, such as the variants of an enum whatever their
+/// parameters, for `GrowthRecorder` to count.
+pub trait Kind {
+  /// Name of the kind, shared by every symbol of that kind and by no other.
+  fn kind(&self) -> &str;
+}
+
+/// Counts the symbols that rules rewrite and those they leave as they were,
+/// through the rules it wraps. Clones share the counts.
+#[derive(Clone, Debug, Default)]
+pub struct Tally {
+  rewritten: Arc<AtomicUsize>,
+  unchanged: Arc<AtomicUsize>,
+}
+
+impl Tally {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Rules of an `LSystem`, counted.
+  pub fn track<T, P>(
+    &self,
+    mut rules: impl FnMut(T, &Context<T>, &P, &mut StdRng) -> Vec<T>,
+  ) -> impl FnMut(T, &Context<T>, &P, &mut StdRng) -> Vec<T>
+  where
+    T: Clone + PartialEq,
+  {
+    let tally = self.clone();
+    move |symbol, context, constants, rng| {
+      let successor = rules(symbol.clone(), context, constants, rng);
+      tally.count(&symbol, &successor);
+      successor
+    }
+  }
+
+  /// Rules of a `Derivation`, counted.
+  pub fn track_sink<T, P>(
+    &self,
+    mut rules: impl FnMut(&T, &Context<T>, &P, &mut StdRng, &mut Sink<T>),
+  ) -> impl FnMut(&T, &Context<T>, &P, &mut StdRng, &mut Sink<T>)
+  where
+    T: PartialEq,
+  {
+    let tally = self.clone();
+    move |symbol, context, constants, rng, sink| {
+      let start = sink.len();
+      rules(symbol, context, constants, rng, sink);
+      tally.count(symbol, &sink.written()[start..]);
+    }
+  }
+
+  fn count<T: PartialEq>(&self, symbol: &T, successor: &[T]) {
+    let counter = if successor.len() == 1 && successor[0] == *symbol {
+      &self.unchanged
+    } else {
+      &self.rewritten
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Counts since the last call, as `(rewritten, unchanged)`.
+  pub fn take(&self) -> (usize, usize) {
+    (
+      self.rewritten.swap(0, Ordering::Relaxed),
+      self.unchanged.swap(0, Ordering::Relaxed),
+    )
+  }
+}
+
+/// What a generation is made of.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepStats {
+  pub generation: usize,
+  pub length: usize,
+  /// Length over that of the previous generation, if there is one and it
+  /// isn't empty.
+  pub growth: Option<f64>,
+  /// Symbols of the previous generation rewritten into something else, when
+  /// the rules are tracked by a `Tally`.
+  pub rewritten: Option<usize>,
+  /// Symbols of the previous generation rewritten into themselves, when the
+  /// rules are tracked by a `Tally`.
+  pub unchanged: Option<usize>,
+  /// Symbols of each kind.
+  pub counts: BTreeMap<String, usize>,
+}
+
+/// How a sequence of counts grows in the long run, as far as the last steps
+/// tell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Growth {
+  /// Settles down, or dies out.
+  Bounded,
+  /// Roughly `generation^degree`.
+  Polynomial { degree: f64 },
+  /// Roughly `ratio^generation`.
+  Exponential { ratio: f64 },
+}
+
+impl Display for Growth {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Growth::Bounded => write!(f, "bounded"),
+      Growth::Polynomial { degree } => write!(f, "polynomial, degree {:.2}", degree),
+      Growth::Exponential { ratio } => write!(f, "exponential, x{:.3} per step", ratio),
+    }
+  }
+}
+
+/// Exponent below which counts are taken for bounded.
+const BOUNDED_DEGREE: f64 = 0.05;
+
+/// Fewest generations an estimate is made from.
+const MIN_SAMPLES: usize = 3;
+
+/// Slope of the least-squares line through `points`, with the sum of the
+/// squared residuals.
+fn fit(points: &[(f64, f64)]) -> (f64, f64) {
+  let count = points.len() as f64;
+  let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+  let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+  let covariance = points
+    .iter()
+    .map(|(x, y)| (x - mean_x) * (y - mean_y))
+    .sum::<f64>();
+  let variance = points
+    .iter()
+    .map(|(x, _)| (x - mean_x).powi(2))
+    .sum::<f64>();
+  let slope = covariance / variance;
+  let residuals = points
+    .iter()
+    .map(|(x, y)| (mean_y + slope * (x - mean_x) - y).powi(2))
+    .sum();
+  (slope, residuals)
+}
+
+/// Fits the second half of `series`, indexed by generation, with a power and
+/// with an exponential in log space, and keeps the closer of the two.
+fn estimate(series: &[usize]) -> Option<Growth> {
+  let tail = &series[series.len() / 2..];
+  if tail.last() == Some(&0) {
+    return (series.len() >= MIN_SAMPLES).then_some(Growth::Bounded);
+  }
+  // Generations are shifted by one so that the axiom's has a logarithm.
+  let samples = tail
+    .iter()
+    .enumerate()
+    .filter(|(_, count)| **count > 0)
+    .map(|(index, count)| ((series.len() / 2 + index + 1) as f64, (*count as f64).ln()))
+    .collect::<Vec<_>>();
+  if samples.len() < MIN_SAMPLES {
+    return None;
+  }
+  let (degree, power_residuals) = fit(
+    &samples
+      .iter()
+      .map(|(generation, count)| (generation.ln(), *count))
+      .collect::<Vec<_>>(),
+  );
+  let (rate, exponential_residuals) = fit(&samples);
+  Some(if degree < BOUNDED_DEGREE {
+    Growth::Bounded
+  } else if exponential_residuals < power_residuals {
+    Growth::Exponential { ratio: rate.exp() }
+  } else {
+    Growth::Polynomial { degree }
+  })
+}
+
+/// Records `StepStats` one generation after the other.
+#[derive(Default)]
+pub struct GrowthRecorder {
+  tally: Option<Tally>,
+  steps: Vec<StepStats>,
+}
+
+impl GrowthRecorder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Reads rewritten and unchanged symbols off `tally`, which should track
+  /// the rules deriving what gets recorded.
+  pub fn with_tally(mut self, tally: Tally) -> Self {
+    self.tally = Some(tally);
+    self
+  }
+
+  /// Records the next generation, starting with the axiom.
+  pub fn record<T: Kind>(&mut self, string: &[T]) {
+    let mut counts = BTreeMap::<String, usize>::new();
+    for symbol in string.iter() {
+      match counts.get_mut(symbol.kind()) {
+        Some(count) => *count += 1,
+        None => {
+          counts.insert(symbol.kind().to_string(), 1);
+        }
+      }
+    }
+    let previous = self.steps.last();
+    let growth = previous
+      .filter(|previous| previous.length > 0)
+      .map(|previous| string.len() as f64 / previous.length as f64);
+    let (rewritten, unchanged) = match (&self.tally, previous) {
+      (Some(tally), Some(_)) => {
+        let (rewritten, unchanged) = tally.take();
+        (Some(rewritten), Some(unchanged))
+      }
+      (Some(tally), None) => {
+        tally.take();
+        (None, None)
+      }
+      (None, _) => (None, None),
+    };
+    self.steps.push(StepStats {
+      generation: self.steps.len(),
+      length: string.len(),
+      growth,
+      rewritten,
+      unchanged,
+      counts,
+    });
+  }
+
+  pub fn into_report(self) -> GrowthReport {
+    GrowthReport { steps: self.steps }
+  }
+}
+
+/// Records every generation in `generations`, such as those of an `LSystem`
+/// taken up to some step.
+pub fn analyze<T, S>(generations: impl IntoIterator<Item = S>) -> GrowthReport
+where
+  T: Kind,
+  S: AsRef<[T]>,
+{
+  let mut recorder = GrowthRecorder::new();
+  for string in generations {
+    recorder.record(string.as_ref());
+  }
+  recorder.into_report()
+}
+
+/// Statistics of every generation derived, from the axiom on.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GrowthReport {
+  pub steps: Vec<StepStats>,
+}
+
+impl GrowthReport {
+  /// Every kind of symbol met, in order.
+  pub fn kinds(&self) -> BTreeSet<&str> {
+    self
+      .steps
+      .iter()
+      .flat_map(|step| step.counts.keys().map(String::as_str))
+      .collect()
+  }
+
+  /// Symbols of `kind` in each generation.
+  pub fn counts_of(&self, kind: &str) -> Vec<usize> {
+    self
+      .steps
+      .iter()
+      .map(|step| step.counts.get(kind).copied().unwrap_or(0))
+      .collect()
+  }
+
+  /// How the length of the string grows, if enough generations were
+  /// recorded to tell.
+  pub fn growth(&self) -> Option<Growth> {
+    estimate(
+      &self
+        .steps
+        .iter()
+        .map(|step| step.length)
+        .collect::<Vec<_>>(),
+    )
+  }
+
+  /// How the number of symbols of `kind` grows.
+  pub fn growth_of(&self, kind: &str) -> Option<Growth> {
+    estimate(&self.counts_of(kind))
+  }
+
+  /// The report as an aligned text table, a row per generation and a column
+  /// per kind, followed by growth estimates.
+  pub fn to_table(&self) -> String {
+    let kinds = self.kinds();
+    let mut header = vec![
+      "generation".to_string(),
+      "length".to_string(),
+      "growth".to_string(),
+      "rewritten".to_string(),
+      "unchanged".to_string(),
+    ];
+    header.extend(kinds.iter().map(|kind| kind.to_string()));
+    let optional = |value: Option<usize>| value.map_or("-".to_string(), |value| value.to_string());
+    let mut rows = vec![header];
+    for step in self.steps.iter() {
+      let mut row = vec![
+        step.generation.to_string(),
+        step.length.to_string(),
+        step
+          .growth
+          .map_or("-".to_string(), |growth| format!("{:.3}", growth)),
+        optional(step.rewritten),
+        optional(step.unchanged),
+      ];
+      row.extend(
+        kinds
+          .iter()
+          .map(|kind| step.counts.get(*kind).copied().unwrap_or(0).to_string()),
+      );
+      rows.push(row);
+    }
+
+    let mut widths = vec![0; rows[0].len()];
+    for row in rows.iter() {
+      for (width, cell) in widths.iter_mut().zip(row.iter()) {
+        *width = (*width).max(cell.len());
+      }
+    }
+    let mut table = String::new();
+    for row in rows.iter() {
+      let cells = row
+        .iter()
+        .zip(widths.iter())
+        .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+        .collect::<Vec<_>>();
+      table.push_str(cells.join("  ").trim_end());
+      table.push('\n');
+    }
+
+    let describe =
+      |growth: Option<Growth>| growth.map_or("unknown".to_string(), |growth| growth.to_string());
+    table.push_str(&format!("\nlength: {}\n", describe(self.growth())));
+    for kind in kinds.iter() {
+      table.push_str(&format!("{}: {}\n", kind, describe(self.growth_of(kind))));
+    }
+    table
+  }
+
+  /// The report as JSON: the statistics of every generation under `steps`,
+  /// and growth estimates under `growth`, for the length and for each kind.
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> Value {
+    let growth = |growth: Option<Growth>| match growth {
+      None => Value::Null,
+      Some(Growth::Bounded) => json!({ "kind": "bounded" }),
+      Some(Growth::Polynomial { degree }) => json!({ "kind": "polynomial", "degree": degree }),
+      Some(Growth::Exponential { ratio }) => json!({ "kind": "exponential", "ratio": ratio }),
+    };
+    let steps = self
+      .steps
+      .iter()
+      .map(|step| {
+        json!({
+          "generation": step.generation,
+          "length": step.length,
+          "growth": step.growth,
+          "rewritten": step.rewritten,
+          "unchanged": step.unchanged,
+          "counts": step.counts,
+        })
+      })
+      .collect::<Vec<_>>();
+    let kinds = self
+      .kinds()
+      .into_iter()
+      .map(|kind| (kind.to_string(), growth(self.growth_of(kind))))
+      .collect::<serde_json::Map<_, _>>();
+    json!({
+      "steps": steps,
+      "growth": {
+        "length": growth(self.growth()),
+        "kinds": kinds,
+      },
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::systems::{grammar::module::Module, leaf::Leaf};
+
+  #[test]
+  fn symbols_are_counted_by_kind() {
+    let report = analyze([vec![
+      Leaf::Grow(1.0, 1.1, None),
+      Leaf::Grow(2.0, 1.1, Some(3.0)),
+      Leaf::SideApex(1.0),
+      Leaf::MainApex(1.0, true),
+    ]]);
+    assert_eq!(
+      report.steps[0].counts,
+      BTreeMap::from([
+        ("Grow".to_string(), 2),
+        ("MainApex".to_string(), 1),
+        ("SideApex".to_string(), 1),
+      ])
+    );
+    let report = analyze([vec![Module::new("F", vec![1.0]), Module::new("F", vec![])]]);
+    assert_eq!(report.counts_of("F"), vec![2]);
+  }
+
+  #[test]
+  fn growth_is_estimated() {
+    let exponential = (0..12).map(|step| 3 << step).collect::<Vec<usize>>();
+    match estimate(&exponential) {
+      Some(Growth::Exponential { ratio }) => assert!((ratio - 2.0).abs() < 1e-6),
+      growth => panic!("{:?}", growth),
+    }
+    let quadratic = (1..=12).map(|step| step * step).collect::<Vec<usize>>();
+    match estimate(&quadratic) {
+      Some(Growth::Polynomial { degree }) => assert!((degree - 2.0).abs() < 1e-6),
+      growth => panic!("{:?}", growth),
+    }
+    assert_eq!(estimate(&[5; 12]), Some(Growth::Bounded));
+    assert_eq!(estimate(&[4, 2, 0]), Some(Growth::Bounded));
+    assert_eq!(estimate(&[1, 2]), None);
+  }
+}
@@ -0,0 +1,214 @@
+use std::{collections::HashSet, mem::Discriminant};
+
+pub enum Bracket {
+  Open,
+  Close,
+}
+
+/// Symbols that can delimit a branch. Context matching skips over branches
+/// the way ABOP does: a left context never looks inside a completed branch and
+/// a right context never leaves the branch the symbol belongs to.
+pub trait Branching {
+  fn bracket(&self) -> Option<Bracket>;
+}
+
+/// Neighbourhood of the symbol being rewritten, handed to the rule alongside
+/// the symbol itself. Symbols whose discriminant is in the ignore-set are
+/// transparent to context matching.
+pub struct Context<'a, T> {
+  string: &'a [T],
+  index: usize,
+  ignored: &'a HashSet<Discriminant<T>>,
+}
+
+impl<'a, T: Branching> Context<'a, T> {
+  pub fn new(string: &'a [T], index: usize, ignored: &'a HashSet<Discriminant<T>>) -> Self {
+    Self {
+      string,
+      index,
+      ignored,
+    }
+  }
+
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  pub fn left(&self) -> Option<&'a T> {
+    self.lefts().next()
+  }
+
+  pub fn right(&self) -> Option<&'a T> {
+    self.rights().next()
+  }
+
+  /// Successive left neighbours, walking down towards the root of the branch.
+  pub fn lefts(&self) -> Lefts<'a, T> {
+    Lefts {
+      string: self.string,
+      position: self.index,
+      ignored: self.ignored,
+    }
+  }
+
+  /// Successive right neighbours, walking up the current branch and stepping
+  /// over any lateral branch met on the way.
+  pub fn rights(&self) -> Rights<'a, T> {
+    Rights {
+      string: self.string,
+      position: self.index,
+      ignored: self.ignored,
+    }
+  }
+}
+
+pub struct Lefts<'a, T> {
+  string: &'a [T],
+  position: usize,
+  ignored: &'a HashSet<Discriminant<T>>,
+}
+
+impl<'a, T: Branching> Iterator for Lefts<'a, T> {
+  type Item = &'a T;
+
+  fn next(&mut self) -> Option<&'a T> {
+    while self.position > 0 {
+      self.position -= 1;
+      let symbol = &self.string[self.position];
+      if self.ignored.contains(&std::mem::discriminant(symbol)) {
+        continue;
+      }
+      match symbol.bracket() {
+        Some(Bracket::Open) => {}
+        Some(Bracket::Close) => {
+          let mut depth = 1;
+          while depth > 0 {
+            if self.position == 0 {
+              return None;
+            }
+            self.position -= 1;
+            match self.string[self.position].bracket() {
+              Some(Bracket::Open) => depth -= 1,
+              Some(Bracket::Close) => depth += 1,
+              None => {}
+            }
+          }
+        }
+        None => return Some(symbol),
+      }
+    }
+    None
+  }
+}
+
+pub struct Rights<'a, T> {
+  string: &'a [T],
+  position: usize,
+  ignored: &'a HashSet<Discriminant<T>>,
+}
+
+impl<'a, T: Branching> Iterator for Rights<'a, T> {
+  type Item = &'a T;
+
+  fn next(&mut self) -> Option<&'a T> {
+    while self.position + 1 < self.string.len() {
+      self.position += 1;
+      let symbol = &self.string[self.position];
+      if self.ignored.contains(&std::mem::discriminant(symbol)) {
+        continue;
+      }
+      match symbol.bracket() {
+        Some(Bracket::Open) => {
+          let mut depth = 1;
+          while depth > 0 {
+            if self.position + 1 >= self.string.len() {
+              return None;
+            }
+            self.position += 1;
+            match self.string[self.position].bracket() {
+              Some(Bracket::Open) => depth += 1,
+              Some(Bracket::Close) => depth -= 1,
+              None => {}
+            }
+          }
+        }
+        Some(Bracket::Close) => {
+          self.position = self.string.len();
+          return None;
+        }
+        None => return Some(symbol),
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, PartialEq)]
+  enum Symbol {
+    Node(char),
+    Turn,
+    Open,
+    Close,
+  }
+
+  impl Branching for Symbol {
+    fn bracket(&self) -> Option<Bracket> {
+      match self {
+        Symbol::Open => Some(Bracket::Open),
+        Symbol::Close => Some(Bracket::Close),
+        _ => None,
+      }
+    }
+  }
+
+  fn symbols(source: &str) -> Vec<Symbol> {
+    source
+      .chars()
+      .map(|c| match c {
+        '+' => Symbol::Turn,
+        '[' => Symbol::Open,
+        ']' => Symbol::Close,
+        c => Symbol::Node(c),
+      })
+      .collect()
+  }
+
+  fn names<'a>(neighbours: impl Iterator<Item = &'a Symbol>) -> String {
+    neighbours
+      .map(|symbol| match symbol {
+        Symbol::Node(c) => *c,
+        _ => '+',
+      })
+      .collect()
+  }
+
+  const STRING: &str = "A[B]+C[D[E]F]G";
+
+  #[test]
+  fn neighbours_skip_branches() {
+    let string = symbols(STRING);
+    let ignored = HashSet::new();
+    let at = |index| Context::new(&string, index, &ignored);
+    assert_eq!(names(at(5).lefts()), "+A");
+    assert_eq!(names(at(5).rights()), "G");
+    assert_eq!(names(at(7).lefts()), "C+A");
+    assert_eq!(names(at(7).rights()), "F");
+    assert_eq!(names(at(11).rights()), "");
+    assert_eq!(at(0).left(), None);
+    assert_eq!(at(13).right(), None);
+  }
+
+  #[test]
+  fn neighbours_skip_ignored_symbols() {
+    let string = symbols(STRING);
+    let ignored = HashSet::from([std::mem::discriminant(&Symbol::Turn)]);
+    let context = Context::new(&string, 5, &ignored);
+    assert_eq!(context.left(), Some(&Symbol::Node('A')));
+    assert_eq!(names(context.lefts()), "A");
+    assert_eq!(context.right(), Some(&Symbol::Node('G')));
+  }
+}
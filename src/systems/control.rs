@@ -0,0 +1,169 @@
+//! Limits and reporting for `Derivation::derive`.
+
+use std::{
+  fmt::Display,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+};
+
+/// Flag stopping a derivation from another thread, or from a progress
+/// callback. Clones share the flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+/// Where a derivation stands, reported between chunks of a step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+  /// Generation being derived.
+  pub generation: usize,
+  /// Symbols of the previous generation rewritten so far.
+  pub rewritten: usize,
+  /// Length of the previous generation.
+  pub length: usize,
+  /// Symbols written so far.
+  pub written: usize,
+}
+
+/// Why a derivation stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+  /// Every step asked for was derived.
+  Completed,
+  /// A step left the string unchanged, so further ones would too.
+  Fixpoint,
+  /// A step would have made the string longer than allowed.
+  MaxLength,
+  /// A step would have made the buffers outgrow the memory budget.
+  MemoryBudget,
+  Cancelled,
+}
+
+impl Display for StopReason {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StopReason::Completed => write!(f, "completed"),
+      StopReason::Fixpoint => write!(f, "reached a fixpoint"),
+      StopReason::MaxLength => write!(f, "reached the maximum length"),
+      StopReason::MemoryBudget => write!(f, "ran out of memory budget"),
+      StopReason::Cancelled => write!(f, "cancelled"),
+    }
+  }
+}
+
+/// How a derivation ended. The string is always a complete generation: a
+/// step stopped half-way is discarded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outcome {
+  pub reason: StopReason,
+  /// Generation of the string kept.
+  pub generation: usize,
+  /// Length of the string kept.
+  pub length: usize,
+}
+
+type ProgressCallback<'a> = Box<dyn FnMut(&Progress) + 'a>;
+type HeapSize<'a, T> = Box<dyn Fn(&T) -> usize + 'a>;
+
+/// Limits of a derivation of symbols `T`, checked between chunks of every
+/// step.
+pub struct DeriveOptions<'a, T> {
+  max_length: Option<usize>,
+  memory_budget: Option<(usize, HeapSize<'a, T>)>,
+  cancellation: Option<CancellationToken>,
+  progress: Option<ProgressCallback<'a>>,
+}
+
+impl<'a, T> Default for DeriveOptions<'a, T> {
+  fn default() -> Self {
+    Self {
+      max_length: None,
+      memory_budget: None,
+      cancellation: None,
+      progress: None,
+    }
+  }
+}
+
+impl<'a, T> DeriveOptions<'a, T> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Longest string a step may write.
+  pub fn with_max_length(mut self, max_length: usize) -> Self {
+    self.max_length = Some(max_length);
+    self
+  }
+
+  /// Bytes the two string buffers may take together, along with what every
+  /// symbol in them owns on the heap according to `heap_size`, such as
+  /// `Module::heap_size` or `|_| 0` for symbols owning nothing. A buffer may
+  /// overshoot by one growth before the budget is checked.
+  pub fn with_memory_budget(mut self, bytes: usize, heap_size: impl Fn(&T) -> usize + 'a) -> Self {
+    self.memory_budget = Some((bytes, Box::new(heap_size)));
+    self
+  }
+
+  pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+    self.cancellation = Some(token);
+    self
+  }
+
+  pub fn with_progress(mut self, progress: impl FnMut(&Progress) + 'a) -> Self {
+    self.progress = Some(Box::new(progress));
+    self
+  }
+
+  /// Heap bytes of `symbols`, if the budget counts them.
+  pub(super) fn heap_size(&self, symbols: &[T]) -> usize {
+    self
+      .memory_budget
+      .as_ref()
+      .map_or(0, |(_, heap_size)| symbols.iter().map(heap_size).sum())
+  }
+
+  /// Reports `progress` and tells whether to stop there.
+  pub(super) fn check(&mut self, progress: &Progress, bytes: usize) -> Option<StopReason> {
+    if let Some(report) = self.progress.as_mut() {
+      report(progress);
+    }
+    if self
+      .cancellation
+      .as_ref()
+      .is_some_and(|token| token.is_cancelled())
+    {
+      Some(StopReason::Cancelled)
+    } else if self
+      .max_length
+      .is_some_and(|max_length| progress.written > max_length)
+    {
+      Some(StopReason::MaxLength)
+    } else if self
+      .memory_budget
+      .as_ref()
+      .is_some_and(|(budget, _)| bytes > *budget)
+    {
+      Some(StopReason::MemoryBudget)
+    } else {
+      None
+    }
+  }
+}
@@ -0,0 +1,503 @@
+use super::{
+  block_rng,
+  context::{Branching, Context},
+  control::{DeriveOptions, Outcome, Progress, StopReason},
+  lineage::Lineage,
+  RNG_BLOCK,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashSet, mem::Discriminant};
+#[cfg(feature = "rayon")]
+use {super::lineage::Step, rayon::prelude::*};
+
+/// Symbols rewritten between two checks of `Derivation::derive`'s limits.
+const CHECK_INTERVAL: usize = 1024;
+
+/// Symbols rewritten together by `Derivation::par_step`.
+#[cfg(feature = "rayon")]
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Where a rule writes the successor of a symbol. Rules can only append, so
+/// they can't disturb what the others wrote.
+pub struct Sink<'a, T> {
+  symbols: &'a mut Vec<T>,
+}
+
+impl<'a, T> Sink<'a, T> {
+  pub fn new(symbols: &'a mut Vec<T>) -> Self {
+    Self { symbols }
+  }
+
+  pub fn push(&mut self, symbol: T) {
+    self.symbols.push(symbol);
+  }
+
+  pub fn extend(&mut self, symbols: impl IntoIterator<Item = T>) {
+    self.symbols.extend(symbols);
+  }
+
+  /// Symbols written so far, the successors of earlier symbols included.
+  pub fn written(&self) -> &[T] {
+    self.symbols
+  }
+
+  pub fn len(&self) -> usize {
+    self.symbols.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.symbols.is_empty()
+  }
+}
+
+/// Derives strings in place, between two buffers swapped at every step, so
+/// that once they have grown no step allocates. Rules borrow the symbol they
+/// rewrite and push its successor into a `Sink` instead of returning it.
+pub struct Derivation<T, F, P>
+where
+  F: FnMut(&T, &Context<T>, &P, &mut StdRng, &mut Sink<T>),
+{
+  current: Vec<T>,
+  next: Vec<T>,
+  generation: usize,
+  rules: F,
+  constants: P,
+  ignored: HashSet<Discriminant<T>>,
+  rng: StdRng,
+  lineage: Option<Lineage>,
+  #[cfg(feature = "rayon")]
+  chunk_size: usize,
+  #[cfg(feature = "rayon")]
+  chunks: Vec<(Vec<T>, Step)>,
+}
+
+impl<T, F, P> Derivation<T, F, P>
+where
+  T: Branching,
+  F: FnMut(&T, &Context<T>, &P, &mut StdRng, &mut Sink<T>),
+{
+  pub fn new(axiom: Vec<T>, rules: F, constants: P) -> Self {
+    Self {
+      current: axiom,
+      next: vec![],
+      generation: 0,
+      rules,
+      constants,
+      ignored: HashSet::new(),
+      rng: StdRng::seed_from_u64(0),
+      lineage: None,
+      #[cfg(feature = "rayon")]
+      chunk_size: DEFAULT_CHUNK_SIZE,
+      #[cfg(feature = "rayon")]
+      chunks: vec![],
+    }
+  }
+
+  /// Same as `LSystem::with_seed`: with the same rules, both engines derive
+  /// the same strings.
+  pub fn with_seed(mut self, seed: u64) -> Self {
+    self.rng = StdRng::seed_from_u64(seed);
+    self
+  }
+
+  /// Same as `LSystem::with_ignored`.
+  pub fn with_ignored(mut self, symbols: &[T]) -> Self {
+    self
+      .ignored
+      .extend(symbols.iter().map(std::mem::discriminant));
+    self
+  }
+
+  /// Same as `LSystem::with_lineage`. Steps `derive` stops half-way aren't
+  /// recorded.
+  pub fn with_lineage(mut self) -> Self {
+    self.lineage = Some(Lineage::starting_at(self.generation));
+    self
+  }
+
+  /// Parents of the symbols derived so far, if recorded.
+  pub fn lineage(&self) -> Option<&Lineage> {
+    self.lineage.as_ref()
+  }
+
+  /// Number of steps derived so far, the axiom being generation 0.
+  pub fn generation(&self) -> usize {
+    self.generation
+  }
+
+  pub fn current(&self) -> &[T] {
+    &self.current
+  }
+
+  pub fn into_current(self) -> Vec<T> {
+    self.current
+  }
+
+  /// Rewrites the current string once and returns the result.
+  pub fn step(&mut self) -> &[T] {
+    self.rewrite(&mut DeriveOptions::new());
+    self.swap();
+    &self.current
+  }
+
+  /// Rewrites the current string into the next buffer, chunk after chunk,
+  /// as long as `options` allow.
+  fn rewrite(&mut self, options: &mut DeriveOptions<T>) -> Option<StopReason> {
+    self.next.clear();
+    if let Some(lineage) = self.lineage.as_mut() {
+      lineage.pending().clear();
+    }
+    let length = self.current.len();
+    let seed = self.rng.gen();
+    let mut rng = block_rng(seed, 0);
+    let mut heap_size = options.heap_size(&self.current);
+    let mut sink = Sink::new(&mut self.next);
+    for start in (0..length).step_by(CHECK_INTERVAL) {
+      let end = (start + CHECK_INTERVAL).min(length);
+      for index in start..end {
+        if index % RNG_BLOCK == 0 {
+          rng = block_rng(seed, index);
+        }
+        let context = Context::new(&self.current, index, &self.ignored);
+        let written = sink.len();
+        (self.rules)(
+          &self.current[index],
+          &context,
+          &self.constants,
+          &mut rng,
+          &mut sink,
+        );
+        if let Some(lineage) = self.lineage.as_mut() {
+          lineage
+            .pending()
+            .record(&self.current[index], sink.written(), written);
+        }
+        heap_size += options.heap_size(&sink.written()[written..]);
+      }
+      let progress = Progress {
+        generation: self.generation + 1,
+        rewritten: end,
+        length,
+        written: sink.len(),
+      };
+      let bytes =
+        (self.current.capacity() + sink.symbols.capacity()) * std::mem::size_of::<T>() + heap_size;
+      if let Some(reason) = options.check(&progress, bytes) {
+        return Some(reason);
+      }
+    }
+    None
+  }
+
+  fn swap(&mut self) {
+    std::mem::swap(&mut self.current, &mut self.next);
+    self.generation += 1;
+    if let Some(lineage) = self.lineage.as_mut() {
+      lineage.commit();
+    }
+  }
+
+  /// Derives up to `steps` more generations within the limits of `options`,
+  /// stopping early at a fixpoint. A step stopped half-way is dropped, but
+  /// the seed it drew isn't drawn again.
+  pub fn derive(&mut self, steps: usize, mut options: DeriveOptions<T>) -> Outcome
+  where
+    T: PartialEq,
+  {
+    let mut reason = StopReason::Completed;
+    for _ in 0..steps {
+      if let Some(stop) = self.rewrite(&mut options) {
+        reason = stop;
+        break;
+      }
+      self.swap();
+      if self.current == self.next {
+        reason = StopReason::Fixpoint;
+        break;
+      }
+    }
+    Outcome {
+      reason,
+      generation: self.generation,
+      length: self.current.len(),
+    }
+  }
+
+  /// Derives `steps` more generations and returns the last one, which is
+  /// what `LSystem::nth(steps)` returns on a fresh system.
+  pub fn advance(&mut self, steps: usize) -> &[T] {
+    for _ in 0..steps {
+      self.step();
+    }
+    &self.current
+  }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, F, P> Derivation<T, F, P>
+where
+  T: Branching + Send + Sync,
+  F: Fn(&T, &Context<T>, &P, &mut StdRng, &mut Sink<T>) + Sync,
+  P: Sync,
+{
+  /// Number of symbols `par_step` rewrites together, rounded up to whole
+  /// blocks of `RNG_BLOCK`.
+  pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+    assert!(chunk_size > 0, "chunks can't be empty");
+    self.chunk_size = chunk_size.next_multiple_of(RNG_BLOCK);
+    self
+  }
+
+  /// `step` with chunks of the string rewritten concurrently, then joined in
+  /// order. Chunks are made of whole blocks of `RNG_BLOCK`, each drawing from
+  /// the same generator as with `step`, so stochastic rules derive the same
+  /// as with `step` too, whatever the chunk size and the number of threads.
+  pub fn par_step(&mut self) -> &[T] {
+    let chunk_size = self.chunk_size;
+    self
+      .chunks
+      .resize_with(self.current.len().div_ceil(chunk_size), Default::default);
+    let seed: u64 = self.rng.gen();
+    let tracked = self.lineage.is_some();
+    let (current, ignored, rules, constants) =
+      (&self.current, &self.ignored, &self.rules, &self.constants);
+    self
+      .chunks
+      .par_iter_mut()
+      .enumerate()
+      .for_each(|(chunk, (successors, step))| {
+        successors.clear();
+        step.clear();
+        let mut sink = Sink::new(successors);
+        let start = chunk * chunk_size;
+        let mut rng = block_rng(seed, start);
+        for index in start..(start + chunk_size).min(current.len()) {
+          if index % RNG_BLOCK == 0 {
+            rng = block_rng(seed, index);
+          }
+          let context = Context::new(current, index, ignored);
+          let written = sink.len();
+          rules(&current[index], &context, constants, &mut rng, &mut sink);
+          if tracked {
+            step.record(&current[index], sink.written(), written);
+          }
+        }
+      });
+    self.next.clear();
+    if let Some(lineage) = self.lineage.as_mut() {
+      lineage.pending().clear();
+    }
+    for (successors, step) in self.chunks.iter_mut() {
+      if let Some(lineage) = self.lineage.as_mut() {
+        lineage.pending().append(step, self.next.len());
+      }
+      self.next.append(successors);
+    }
+    self.swap();
+    &self.current
+  }
+
+  /// `advance` with `par_step`.
+  pub fn par_advance(&mut self, steps: usize) -> &[T] {
+    for _ in 0..steps {
+      self.par_step();
+    }
+    &self.current
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::systems::{
+    context::Bracket, control::CancellationToken, grammar::module::Module, LSystem,
+  };
+
+  #[derive(Clone, Debug, PartialEq)]
+  enum Symbol {
+    Apex,
+    Segment(u32),
+  }
+
+  impl Branching for Symbol {
+    fn bracket(&self) -> Option<Bracket> {
+      None
+    }
+  }
+
+  /// Apices branch or grow at random, segments get a random length.
+  fn stochastic_rule(
+    symbol: &Symbol,
+    _: &Context<Symbol>,
+    _: &(),
+    rng: &mut StdRng,
+    sink: &mut Sink<Symbol>,
+  ) {
+    match symbol {
+      Symbol::Apex if rng.gen_bool(0.5) => sink.extend([
+        Symbol::Segment(rng.gen_range(1..10)),
+        Symbol::Apex,
+        Symbol::Apex,
+      ]),
+      Symbol::Apex => sink.extend([Symbol::Segment(rng.gen_range(1..10)), Symbol::Apex]),
+      Symbol::Segment(length) => sink.push(Symbol::Segment(length + rng.gen_range(0..2))),
+    }
+  }
+
+  const STEPS: usize = 16;
+
+  fn sequential() -> Vec<Symbol> {
+    Derivation::new(vec![Symbol::Apex], stochastic_rule, ())
+      .with_seed(7)
+      .advance(STEPS)
+      .to_vec()
+  }
+
+  /// Apices double at every step.
+  fn doubling_rule(
+    symbol: &Symbol,
+    _: &Context<Symbol>,
+    _: &(),
+    _: &mut StdRng,
+    sink: &mut Sink<Symbol>,
+  ) {
+    match symbol {
+      Symbol::Apex => sink.extend([Symbol::Apex, Symbol::Apex]),
+      symbol => sink.push(symbol.clone()),
+    }
+  }
+
+  #[test]
+  fn derive_stops_at_a_fixpoint() {
+    let mut derivation = Derivation::new(
+      vec![Symbol::Apex, Symbol::Apex],
+      |symbol: &Symbol, _: &Context<Symbol>, _: &(), _: &mut StdRng, sink: &mut Sink<Symbol>| {
+        sink.push(match symbol {
+          Symbol::Apex => Symbol::Segment(1),
+          symbol => symbol.clone(),
+        })
+      },
+      (),
+    );
+    let outcome = derivation.derive(10, DeriveOptions::new());
+    assert_eq!(
+      outcome,
+      Outcome {
+        reason: StopReason::Fixpoint,
+        generation: 2,
+        length: 2,
+      }
+    );
+    assert_eq!(
+      derivation.current(),
+      [Symbol::Segment(1), Symbol::Segment(1)]
+    );
+  }
+
+  #[test]
+  fn derive_stops_before_exceeding_the_max_length() {
+    let mut derivation = Derivation::new(vec![Symbol::Apex], doubling_rule, ());
+    let outcome = derivation.derive(20, DeriveOptions::new().with_max_length(100));
+    assert_eq!(
+      outcome,
+      Outcome {
+        reason: StopReason::MaxLength,
+        generation: 6,
+        length: 64,
+      }
+    );
+    assert_eq!(
+      derivation.derive(3, DeriveOptions::new()).reason,
+      StopReason::Completed
+    );
+    assert_eq!(derivation.current().len(), 512);
+  }
+
+  #[test]
+  fn derive_stops_when_cancelled() {
+    let token = CancellationToken::new();
+    let mut reported = vec![];
+    let mut derivation = Derivation::new(vec![Symbol::Apex], doubling_rule, ());
+    let options = DeriveOptions::new()
+      .with_cancellation(token.clone())
+      .with_progress(|progress| {
+        reported.push(progress.generation);
+        if progress.generation == 3 {
+          token.cancel();
+        }
+      });
+    let outcome = derivation.derive(10, options);
+    assert_eq!(outcome.reason, StopReason::Cancelled);
+    assert_eq!(outcome.generation, 2);
+    assert_eq!(derivation.current().len(), 4);
+    assert_eq!(reported, [1, 2, 3]);
+  }
+
+  #[test]
+  fn memory_budget_counts_what_symbols_own() {
+    let derivation = || {
+      Derivation::new(
+        vec![Module::new("A", vec![0.0; 125])],
+        |module: &Module, _: &Context<Module>, _: &(), _: &mut StdRng, sink: &mut Sink<Module>| {
+          sink.extend([module.clone(), module.clone()])
+        },
+        (),
+      )
+    };
+    let outcome = derivation().derive(5, DeriveOptions::new().with_memory_budget(30_000, |_| 0));
+    assert_eq!(outcome.reason, StopReason::Completed);
+    let outcome = derivation().derive(
+      5,
+      DeriveOptions::new().with_memory_budget(30_000, Module::heap_size),
+    );
+    assert_eq!(outcome.reason, StopReason::MemoryBudget);
+    assert_eq!(outcome.generation, 4);
+  }
+
+  #[test]
+  fn advance_matches_l_system_nth() {
+    use crate::systems::leaf::{leaf_rule, leaf_rule_into, LeafParameters, LEAF_AXIOM};
+    use rand::distributions::{Distribution, Standard};
+
+    let parameters: LeafParameters = Standard.sample(&mut StdRng::seed_from_u64(5));
+    let mut l_system = LSystem::new(LEAF_AXIOM.to_vec(), leaf_rule, parameters.clone());
+    let mut derivation = Derivation::new(LEAF_AXIOM.to_vec(), leaf_rule_into, parameters);
+    assert_eq!(derivation.advance(0), &l_system.next().unwrap()[..]);
+    assert_eq!(derivation.advance(12), &l_system.nth(11).unwrap()[..]);
+    assert_eq!(derivation.generation(), 12);
+  }
+
+  #[test]
+  fn step_matches_l_system_with_stochastic_rules() {
+    let mut l_system = LSystem::new(
+      vec![Symbol::Apex],
+      |symbol, context: &Context<Symbol>, constants, rng| {
+        let mut successor = vec![];
+        stochastic_rule(
+          &symbol,
+          context,
+          constants,
+          rng,
+          &mut Sink::new(&mut successor),
+        );
+        successor
+      },
+      (),
+    )
+    .with_seed(7);
+    assert_eq!(l_system.nth(STEPS).unwrap(), sequential());
+  }
+
+  #[cfg(feature = "rayon")]
+  #[test]
+  fn par_step_matches_step_with_stochastic_rules() {
+    let expected = sequential();
+    assert!(expected.len() > 4 * RNG_BLOCK);
+    for chunk_size in [1, RNG_BLOCK, 100, 1000, DEFAULT_CHUNK_SIZE] {
+      let mut derivation = Derivation::new(vec![Symbol::Apex], stochastic_rule, ())
+        .with_seed(7)
+        .with_chunk_size(chunk_size);
+      assert_eq!(derivation.par_advance(STEPS), &expected[..]);
+    }
+  }
+}
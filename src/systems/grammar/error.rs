@@ -0,0 +1,26 @@
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+  pub line: usize,
+  pub column: usize,
+  pub message: String,
+}
+
+impl ParseError {
+  pub fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+    Self {
+      line,
+      column,
+      message: message.into(),
+    }
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}: {}", self.line, self.column, self.message)
+  }
+}
+
+impl std::error::Error for ParseError {}
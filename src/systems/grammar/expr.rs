@@ -0,0 +1,173 @@
+use rand::{rngs::StdRng, Rng};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function {
+  Sqrt,
+  Abs,
+  Sin,
+  Cos,
+  Tan,
+  Asin,
+  Acos,
+  Atan,
+  Atan2,
+  Exp,
+  Ln,
+  Floor,
+  Ceil,
+  Round,
+  Min,
+  Max,
+  Pow,
+  Ran,
+  Uniform,
+}
+
+impl Function {
+  pub fn from_name(name: &str) -> Option<Function> {
+    Some(match name {
+      "sqrt" => Function::Sqrt,
+      "abs" => Function::Abs,
+      "sin" => Function::Sin,
+      "cos" => Function::Cos,
+      "tan" => Function::Tan,
+      "asin" => Function::Asin,
+      "acos" => Function::Acos,
+      "atan" => Function::Atan,
+      "atan2" => Function::Atan2,
+      "exp" => Function::Exp,
+      "ln" | "log" => Function::Ln,
+      "floor" => Function::Floor,
+      "ceil" => Function::Ceil,
+      "round" => Function::Round,
+      "min" => Function::Min,
+      "max" => Function::Max,
+      "pow" => Function::Pow,
+      "ran" => Function::Ran,
+      "uniform" => Function::Uniform,
+      _ => return None,
+    })
+  }
+
+  pub fn arity(&self) -> usize {
+    match self {
+      Function::Atan2 | Function::Min | Function::Max | Function::Pow | Function::Uniform => 2,
+      _ => 1,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+  Neg,
+  Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+  Pow,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  Eq,
+  Ne,
+  And,
+  Or,
+}
+
+/// Arithmetic over `f64`, booleans being `1.0` and `0.0`. Parameters and
+/// constants are resolved to slots at parse time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+  Number(f64),
+  Param(usize),
+  Constant(usize),
+  Unary(UnaryOp, Box<Expr>),
+  Binary(BinaryOp, Box<Expr>, Box<Expr>),
+  Call(Function, Vec<Expr>),
+}
+
+fn truth(value: bool) -> f64 {
+  if value {
+    1.0
+  } else {
+    0.0
+  }
+}
+
+impl Expr {
+  pub fn eval(&self, params: &[f64], constants: &[f64], rng: &mut StdRng) -> f64 {
+    match self {
+      Expr::Number(value) => *value,
+      Expr::Param(slot) => params[*slot],
+      Expr::Constant(slot) => constants[*slot],
+      Expr::Unary(op, operand) => {
+        let value = operand.eval(params, constants, rng);
+        match op {
+          UnaryOp::Neg => -value,
+          UnaryOp::Not => truth(value == 0.0),
+        }
+      }
+      Expr::Binary(BinaryOp::And, lhs, rhs) => {
+        truth(lhs.eval(params, constants, rng) != 0.0 && rhs.eval(params, constants, rng) != 0.0)
+      }
+      Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+        truth(lhs.eval(params, constants, rng) != 0.0 || rhs.eval(params, constants, rng) != 0.0)
+      }
+      Expr::Binary(op, lhs, rhs) => {
+        let lhs = lhs.eval(params, constants, rng);
+        let rhs = rhs.eval(params, constants, rng);
+        match op {
+          BinaryOp::Add => lhs + rhs,
+          BinaryOp::Sub => lhs - rhs,
+          BinaryOp::Mul => lhs * rhs,
+          BinaryOp::Div => lhs / rhs,
+          BinaryOp::Rem => lhs % rhs,
+          BinaryOp::Pow => lhs.powf(rhs),
+          BinaryOp::Lt => truth(lhs < rhs),
+          BinaryOp::Le => truth(lhs <= rhs),
+          BinaryOp::Gt => truth(lhs > rhs),
+          BinaryOp::Ge => truth(lhs >= rhs),
+          BinaryOp::Eq => truth(lhs == rhs),
+          BinaryOp::Ne => truth(lhs != rhs),
+          BinaryOp::And | BinaryOp::Or => unreachable!(),
+        }
+      }
+      Expr::Call(function, args) => {
+        let a = args
+          .first()
+          .map_or(0.0, |arg| arg.eval(params, constants, rng));
+        let b = args
+          .get(1)
+          .map_or(0.0, |arg| arg.eval(params, constants, rng));
+        match function {
+          Function::Sqrt => a.sqrt(),
+          Function::Abs => a.abs(),
+          Function::Sin => a.sin(),
+          Function::Cos => a.cos(),
+          Function::Tan => a.tan(),
+          Function::Asin => a.asin(),
+          Function::Acos => a.acos(),
+          Function::Atan => a.atan(),
+          Function::Atan2 => a.atan2(b),
+          Function::Exp => a.exp(),
+          Function::Ln => a.ln(),
+          Function::Floor => a.floor(),
+          Function::Ceil => a.ceil(),
+          Function::Round => a.round(),
+          Function::Min => a.min(b),
+          Function::Max => a.max(b),
+          Function::Pow => a.powf(b),
+          Function::Ran => a * rng.gen::<f64>(),
+          Function::Uniform => a + (b - a) * rng.gen::<f64>(),
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,194 @@
+use super::error::ParseError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TokenKind {
+  Ident(String),
+  Number(f64),
+  Punct(char),
+  Newline,
+  Eof,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Token {
+  pub kind: TokenKind,
+  pub line: usize,
+  pub column: usize,
+  pub start: usize,
+  pub end: usize,
+}
+
+impl Token {
+  pub fn error(&self, message: impl Into<String>) -> ParseError {
+    ParseError::new(self.line, self.column, message)
+  }
+
+  /// Whether `next` starts right where this token ends, which is how
+  /// two-character operators such as `->` or `<=` are recognized.
+  pub fn touches(&self, next: &Token) -> bool {
+    self.end == next.start
+  }
+}
+
+/// Splits `source` into identifiers, numbers, single punctuation characters
+/// and line breaks, skipping `/* */` comments. Multi-character operators are
+/// left to the parser since `&&` may as well be two pitch commands in a
+/// successor. Comments can't be `//` ones for the same reason: `/` rolls
+/// right, while `*` is no module.
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+  let mut tokens = vec![];
+  let mut chars = source.char_indices().peekable();
+  let (mut line, mut column) = (1, 1);
+  while let Some(&(start, c)) = chars.peek() {
+    let (token_line, token_column) = (line, column);
+    let kind = if c == '\n' {
+      chars.next();
+      line += 1;
+      column = 1;
+      TokenKind::Newline
+    } else if c.is_whitespace() {
+      chars.next();
+      column += 1;
+      continue;
+    } else if source[start..].starts_with("/*") {
+      let Some(end) = source[start + 2..].find("*/").map(|end| start + end + 4) else {
+        return Err(ParseError::new(line, column, "unterminated comment"));
+      };
+      while chars.peek().is_some_and(|&(index, _)| index < end) {
+        if chars.next().is_some_and(|(_, c)| c == '\n') {
+          line += 1;
+          column = 1;
+        } else {
+          column += 1;
+        }
+      }
+      continue;
+    } else if c.is_ascii_digit() {
+      let end = number_end(source, start);
+      let text = &source[start..end];
+      while chars.peek().is_some_and(|&(index, _)| index < end) {
+        chars.next();
+        column += 1;
+      }
+      TokenKind::Number(text.parse().map_err(|_| {
+        ParseError::new(
+          token_line,
+          token_column,
+          format!("invalid number `{}`", text),
+        )
+      })?)
+    } else if c.is_alphabetic() || c == '_' {
+      let mut name = String::new();
+      while let Some(&(_, c)) = chars.peek() {
+        if !(c.is_alphanumeric() || c == '_') {
+          break;
+        }
+        name.push(c);
+        chars.next();
+        column += 1;
+      }
+      TokenKind::Ident(name)
+    } else if c.is_ascii_punctuation() {
+      chars.next();
+      column += 1;
+      TokenKind::Punct(c)
+    } else {
+      return Err(ParseError::new(
+        line,
+        column,
+        format!("unexpected character `{}`", c),
+      ));
+    };
+    let end = chars.peek().map_or(source.len(), |&(index, _)| index);
+    tokens.push(Token {
+      kind,
+      line: token_line,
+      column: token_column,
+      start,
+      end,
+    });
+  }
+  tokens.push(Token {
+    kind: TokenKind::Eof,
+    line,
+    column,
+    start: source.len(),
+    end: source.len(),
+  });
+  Ok(tokens)
+}
+
+fn number_end(source: &str, start: usize) -> usize {
+  let bytes = source.as_bytes();
+  let digits = |mut index: usize| {
+    while index < bytes.len() && bytes[index].is_ascii_digit() {
+      index += 1;
+    }
+    index
+  };
+  let mut end = digits(start);
+  if end + 1 < bytes.len() && bytes[end] == b'.' && bytes[end + 1].is_ascii_digit() {
+    end = digits(end + 1);
+  }
+  if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+    let mut exponent = end + 1;
+    if exponent < bytes.len() && (bytes[exponent] == b'+' || bytes[exponent] == b'-') {
+      exponent += 1;
+    }
+    if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+      end = digits(exponent);
+    }
+  }
+  end
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kinds(source: &str) -> Vec<TokenKind> {
+    tokenize(source)
+      .unwrap()
+      .into_iter()
+      .map(|token| token.kind)
+      .collect()
+  }
+
+  #[test]
+  fn slashes_are_modules() {
+    use TokenKind::{Eof, Ident, Punct};
+    assert_eq!(
+      kinds("A -> F // A"),
+      [
+        Ident("A".into()),
+        Punct('-'),
+        Punct('>'),
+        Ident("F".into()),
+        Punct('/'),
+        Punct('/'),
+        Ident("A".into()),
+        Eof,
+      ]
+    );
+  }
+
+  #[test]
+  fn comments_are_skipped() {
+    let tokens = tokenize("/* a\n comment */ A /*/ */ B").unwrap();
+    let positions: Vec<_> = tokens
+      .iter()
+      .map(|token| (token.kind.clone(), token.line, token.column))
+      .collect();
+    assert_eq!(
+      positions,
+      [
+        (TokenKind::Ident("A".into()), 2, 13),
+        (TokenKind::Ident("B".into()), 2, 22),
+        (TokenKind::Eof, 2, 23),
+      ]
+    );
+    let error = tokenize("A\n B /* */ /*/").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 10));
+    assert_eq!(error.message, "unterminated comment");
+  }
+}
@@ -0,0 +1,343 @@
+//! Textual parametric L-systems in the spirit of cpfg and L-Py:
+//!
+//! ```text
+//! #define R 1.2
+//! module A(t, d), B(t), G(l, r)
+//! ignore: + -
+//! axiom: A(0, 1)
+//! A(t, d) : t > 1 -> G(5, R) [ + B(t) ] A(t + 1, d)
+//! A(t, d) -> A(t + 1, d) : 0.3
+//! A(t, d) -> B(t) A(t + 1, d) : 0.7
+//! G(l, r) < B(t) -> B(t + l)
+//! ```
+//!
+//! Module names are identifiers, which must be declared with their arity,
+//! or single punctuation characters such as `+` or `[`, which need not be.
+//! Successor modules are separated by whitespace. A production may have a
+//! left context (`L < P`), a right context (`P > R`), a condition after `:`
+//! and a weight after its successor. The first production matching a module
+//! is applied, unless it has a weight, in which case one of all the weighted
+//! productions matching is drawn. Comments go between `/*` and `*/`.
+
+pub mod error;
+pub mod expr;
+mod lexer;
+pub mod module;
+pub mod notation;
+mod parser;
+
+use super::{context::Context, stochastic::pick_index, LSystem};
+use error::ParseError;
+use expr::Expr;
+use module::Module;
+use parser::Parser;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{str::FromStr, sync::Arc};
+
+pub(crate) struct Pattern {
+  name: Arc<str>,
+  arity: usize,
+}
+
+impl Pattern {
+  fn matches(&self, module: &Module) -> bool {
+    self.name == module.name && self.arity == module.params.len()
+  }
+}
+
+pub(crate) struct Template {
+  name: Arc<str>,
+  args: Vec<Expr>,
+}
+
+impl Template {
+  fn instantiate(&self, params: &[f64], constants: &[f64], rng: &mut StdRng) -> Module {
+    Module {
+      name: self.name.clone(),
+      params: self
+        .args
+        .iter()
+        .map(|arg| arg.eval(params, constants, rng))
+        .collect(),
+    }
+  }
+}
+
+/// Formal parameters are bound to consecutive slots: those of the left
+/// context first, then the predecessor's, then the right context's.
+pub(crate) struct Production {
+  left: Option<Pattern>,
+  predecessor: Pattern,
+  right: Option<Pattern>,
+  condition: Option<Expr>,
+  successor: Vec<Template>,
+  weight: Option<Expr>,
+}
+
+pub struct Grammar {
+  defines: Vec<(String, Expr)>,
+  axiom: Vec<Template>,
+  ignored: Vec<Arc<str>>,
+  productions: Vec<Production>,
+}
+
+impl Grammar {
+  pub fn parse(source: &str) -> Result<Grammar, ParseError> {
+    Parser::new(source)?.grammar()
+  }
+
+  /// Overrides a `#define`d constant. Returns whether the grammar defines it.
+  pub fn define(&mut self, name: &str, value: f64) -> bool {
+    match self.defines.iter_mut().find(|(define, _)| define == name) {
+      Some((_, expr)) => {
+        *expr = Expr::Number(value);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Evaluates the constants and the axiom with a generator seeded with
+  /// `seed`, then hands the productions over to an `LSystem` seeded from the
+  /// same generator, so that they don't draw the same numbers again.
+  #[allow(clippy::type_complexity)]
+  pub fn into_lsystem(
+    self,
+    seed: u64,
+  ) -> LSystem<
+    Module,
+    impl FnMut(Module, &Context<Module>, &Vec<f64>, &mut StdRng) -> Vec<Module>,
+    Vec<f64>,
+  > {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut constants = vec![];
+    for (_, expr) in self.defines.iter() {
+      let value = expr.eval(&[], &constants, &mut rng);
+      constants.push(value);
+    }
+    let axiom = self
+      .axiom
+      .iter()
+      .map(|template| template.instantiate(&[], &constants, &mut rng))
+      .collect();
+    let mut rules = Rules {
+      productions: self.productions,
+      ignored: self.ignored,
+      bindings: vec![],
+      candidates: vec![],
+      weights: vec![],
+    };
+    LSystem::new(
+      axiom,
+      move |module, context, constants: &Vec<f64>, rng| {
+        rules.rewrite(module, context, constants, rng)
+      },
+      constants,
+    )
+    .with_seed(rng.gen())
+  }
+}
+
+impl FromStr for Grammar {
+  type Err = ParseError;
+
+  fn from_str(source: &str) -> Result<Self, Self::Err> {
+    Grammar::parse(source)
+  }
+}
+
+struct Rules {
+  productions: Vec<Production>,
+  ignored: Vec<Arc<str>>,
+  bindings: Vec<f64>,
+  candidates: Vec<usize>,
+  weights: Vec<f64>,
+}
+
+fn bind(
+  production: &Production,
+  ignored: &[Arc<str>],
+  module: &Module,
+  context: &Context<Module>,
+  bindings: &mut Vec<f64>,
+) -> bool {
+  bindings.clear();
+  let visible = |neighbour: &&Module| !ignored.contains(&neighbour.name);
+  if let Some(pattern) = &production.left {
+    match context.lefts().find(visible) {
+      Some(neighbour) if pattern.matches(neighbour) => bindings.extend(&neighbour.params),
+      _ => return false,
+    }
+  }
+  if !production.predecessor.matches(module) {
+    return false;
+  }
+  bindings.extend(&module.params);
+  if let Some(pattern) = &production.right {
+    match context.rights().find(visible) {
+      Some(neighbour) if pattern.matches(neighbour) => bindings.extend(&neighbour.params),
+      _ => return false,
+    }
+  }
+  true
+}
+
+impl Rules {
+  fn rewrite(
+    &mut self,
+    module: Module,
+    context: &Context<Module>,
+    constants: &[f64],
+    rng: &mut StdRng,
+  ) -> Vec<Module> {
+    self.candidates.clear();
+    self.weights.clear();
+    for (index, production) in self.productions.iter().enumerate() {
+      if !bind(
+        production,
+        &self.ignored,
+        &module,
+        context,
+        &mut self.bindings,
+      ) {
+        continue;
+      }
+      if let Some(condition) = &production.condition {
+        if condition.eval(&self.bindings, constants, rng) == 0.0 {
+          continue;
+        }
+      }
+      match &production.weight {
+        None if self.candidates.is_empty() => {
+          return self.instantiate(index, constants, rng);
+        }
+        None => {}
+        Some(weight) => {
+          self.candidates.push(index);
+          self
+            .weights
+            .push(weight.eval(&self.bindings, constants, rng));
+        }
+      }
+    }
+    if self.candidates.is_empty() {
+      return vec![module];
+    }
+    let index = self.candidates[pick_index(rng, &self.weights)];
+    bind(
+      &self.productions[index],
+      &self.ignored,
+      &module,
+      context,
+      &mut self.bindings,
+    );
+    self.instantiate(index, constants, rng)
+  }
+
+  fn instantiate(&self, index: usize, constants: &[f64], rng: &mut StdRng) -> Vec<Module> {
+    self.productions[index]
+      .successor
+      .iter()
+      .map(|template| template.instantiate(&self.bindings, constants, rng))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use notation::to_notation;
+
+  fn derive(source: &str, seed: u64, steps: usize) -> String {
+    let mut l_system = Grammar::parse(source).unwrap().into_lsystem(seed);
+    to_notation(&l_system.nth(steps).unwrap())
+  }
+
+  #[test]
+  fn slashes_roll_rather_than_comment() {
+    assert_eq!(
+      derive("module A, F\naxiom: A\nA -> F // A", 0, 1),
+      "F / / A"
+    );
+    assert_eq!(
+      derive(
+        "module A, F\naxiom: A /* sprout */\nA -> F /* grow */ A",
+        0,
+        2
+      ),
+      "F F A"
+    );
+  }
+
+  const EXAMPLE: &str = "
+    #define R 1.2
+    module A(t, d), B(t), G(l, r)
+    ignore: + -
+    axiom: A(0, 1)
+    A(t, d) : t > 1 -> G(5, R) [ + B(t) ] A(t + 1, d)
+    A(t, d) -> A(t + 1, d) : 0.3
+    A(t, d) -> B(t) A(t + 1, d) : 0.7
+    G(l, r) < B(t) -> B(t + l)
+  ";
+
+  #[test]
+  fn example_derives_step_by_step() {
+    assert_eq!(
+      derive(EXAMPLE, 0, 5),
+      "B(0) B(1) G(5, 1.2) [ + B(12) ] G(5, 1.2) [ + B(8) ] G(5, 1.2) [ + B(4) ] A(5, 1)"
+    );
+  }
+
+  #[test]
+  fn conditions_pick_productions() {
+    let source = "module A(t), B\naxiom: A(0)\nA(t) : t < 2 -> A(t + 1)\nA(t) -> B";
+    assert_eq!(derive(source, 0, 2), "A(2)");
+    assert_eq!(derive(source, 0, 3), "B");
+  }
+
+  #[test]
+  fn contexts_skip_branches_and_ignored_modules() {
+    let source = "module A, B, C, X\naxiom: A + [ X ] B + C\nA < B > C -> X";
+    assert_eq!(derive(source, 0, 1), "A + [ X ] B + C");
+    assert_eq!(
+      derive(&format!("ignore: +\n{}", source), 0, 1),
+      "A + [ X ] X + C"
+    );
+  }
+
+  #[test]
+  fn weights_draw_the_same_for_a_seed() {
+    let source = "module A, B, C\naxiom: A A A A A A A A\nA -> B : 1\nA -> C : 2";
+    let derived: Vec<_> = (0..4).map(|seed| derive(source, seed, 1)).collect();
+    for (seed, string) in derived.iter().enumerate() {
+      assert_eq!(derive(source, seed as u64, 1), *string);
+      assert!(string.contains('B') && string.contains('C'), "{}", string);
+    }
+    assert!(derived.iter().any(|string| *string != derived[0]));
+  }
+
+  #[test]
+  fn defines_can_be_overridden() {
+    let source = "#define R 2\nmodule A(x)\naxiom: A(R)\nA(x) -> A(x * R)";
+    let mut grammar = Grammar::parse(source).unwrap();
+    assert!(grammar.define("R", 3.0));
+    assert!(!grammar.define("Q", 1.0));
+    let mut l_system = grammar.into_lsystem(0);
+    assert_eq!(to_notation(&l_system.nth(1).unwrap()), "A(9)");
+    assert_eq!(derive(source, 0, 1), "A(4)");
+  }
+
+  #[test]
+  fn operators_bind_by_precedence() {
+    let source =
+      "module A(a, b, c, d, e)\naxiom: A(-2^2, 2^3^2, 2-3-4, 1 || 0 && 0, 1 + 2 * 3 > 6)";
+    assert_eq!(derive(source, 0, 0), "A(-4, 512, -5, 1, 1)");
+  }
+
+  #[test]
+  fn arrows_end_binary_minus() {
+    let source = "module A(t), B\naxiom: A(3)\nA(t) : t-1>0->A(t-1)\nA(t)->B";
+    assert_eq!(derive(source, 0, 2), "A(1)");
+    assert_eq!(derive(source, 0, 3), "B");
+  }
+}
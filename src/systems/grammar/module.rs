@@ -0,0 +1,98 @@
+use crate::systems::{
+  analysis::Kind,
+  context::{Bracket, Branching},
+};
+use crate::turtle::polygon::{TurtlePolygon, TurtlePolygonInterpretation};
+use std::{fmt::Display, sync::Arc};
+
+/// Module of a grammar known only at runtime: a name and its actual
+/// parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+  pub name: Arc<str>,
+  pub params: Vec<f64>,
+}
+
+impl Module {
+  pub fn new(name: impl Into<Arc<str>>, params: Vec<f64>) -> Self {
+    Self {
+      name: name.into(),
+      params,
+    }
+  }
+
+  /// Bytes the module owns on the heap, for `DeriveOptions::with_memory_budget`.
+  /// Names aren't counted: modules share them with their grammar.
+  pub fn heap_size(&self) -> usize {
+    self.params.capacity() * std::mem::size_of::<f64>()
+  }
+
+  fn param(&self, index: usize, default: f64) -> f64 {
+    self.params.get(index).copied().unwrap_or(default)
+  }
+}
+
+impl Display for Module {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name)?;
+    if !self.params.is_empty() {
+      write!(f, "(")?;
+      for (index, param) in self.params.iter().enumerate() {
+        if index > 0 {
+          write!(f, ", ")?;
+        }
+        write!(f, "{}", param)?;
+      }
+      write!(f, ")")?;
+    }
+    Ok(())
+  }
+}
+
+impl Branching for Module {
+  fn bracket(&self) -> Option<Bracket> {
+    match &*self.name {
+      "[" => Some(Bracket::Open),
+      "]" => Some(Bracket::Close),
+      _ => None,
+    }
+  }
+}
+
+/// Modules are told apart by name, parameters being part of the module.
+impl Kind for Module {
+  fn kind(&self) -> &str {
+    &self.name
+  }
+}
+
+impl TurtlePolygonInterpretation for Module {
+  fn to_turtle(&self) -> TurtlePolygon {
+    match &*self.name {
+      "F" => TurtlePolygon::Forward(self.param(0, 1.0)),
+      "f" => TurtlePolygon::Move(self.param(0, 1.0)),
+      "." => TurtlePolygon::Vertex,
+      "+" => TurtlePolygon::Left,
+      "-" => TurtlePolygon::Right,
+      "&" => TurtlePolygon::PitchDown,
+      "^" => TurtlePolygon::PitchUp,
+      "\\" => TurtlePolygon::RollLeft,
+      "/" => TurtlePolygon::RollRight,
+      "|" => TurtlePolygon::TurnAround,
+      "$" => TurtlePolygon::RollHorizontal,
+      "!" if self.params.is_empty() => TurtlePolygon::DecrementWidth,
+      "!" => TurtlePolygon::SetWidth(self.params[0]),
+      "'" if self.params.is_empty() => TurtlePolygon::NextColor,
+      "'" => TurtlePolygon::SetColor(self.params[0].max(0.0) as usize),
+      ";" => TurtlePolygon::IncrementAngle,
+      "," => TurtlePolygon::DecrementAngle,
+      "\"" => TurtlePolygon::ScaleStep,
+      "@" => TurtlePolygon::SetSusceptibility(self.param(0, 0.0)),
+      "[" => TurtlePolygon::Push,
+      "]" => TurtlePolygon::Pop,
+      "{" => TurtlePolygon::NewPolygon,
+      "}" => TurtlePolygon::ClosePolygon,
+      _ => TurtlePolygon::None,
+    }
+  }
+}
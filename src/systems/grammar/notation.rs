@@ -0,0 +1,106 @@
+//! Bracketed string notation, as printed by the `Display` of symbols such as
+//! `Leaf` or `Module`: `A(0, true) [ + B(1.5) ] G(5, 1.1)`. Whitespace between
+//! symbols is optional.
+
+use super::{
+  error::ParseError,
+  lexer::{tokenize, Token, TokenKind},
+};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+  Number(f64),
+  Bool(bool),
+}
+
+/// Symbol read from a string, along with where it starts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+  pub name: String,
+  pub args: Vec<Value>,
+  pub line: usize,
+  pub column: usize,
+}
+
+impl Symbol {
+  pub fn error(&self, message: impl Into<String>) -> ParseError {
+    ParseError::new(self.line, self.column, message)
+  }
+}
+
+fn unexpected(token: &Token, expected: &str) -> ParseError {
+  let found = match &token.kind {
+    TokenKind::Ident(name) => format!("`{}`", name),
+    TokenKind::Number(value) => format!("`{}`", value),
+    TokenKind::Punct(c) => format!("`{}`", c),
+    TokenKind::Newline => "end of line".to_string(),
+    TokenKind::Eof => "end of input".to_string(),
+  };
+  token.error(format!("expected {}, found {}", expected, found))
+}
+
+pub fn parse_symbols(source: &str) -> Result<Vec<Symbol>, ParseError> {
+  let tokens = tokenize(source)?
+    .into_iter()
+    .filter(|token| token.kind != TokenKind::Newline)
+    .collect::<Vec<_>>();
+  let mut tokens = tokens.iter().peekable();
+  let mut symbols = vec![];
+  while let Some(token) = tokens.next() {
+    let name = match &token.kind {
+      TokenKind::Eof => break,
+      TokenKind::Ident(name) => name.clone(),
+      TokenKind::Punct(c) if *c != '(' && *c != ')' => c.to_string(),
+      _ => return Err(unexpected(token, "a symbol")),
+    };
+    let mut args = vec![];
+    if tokens
+      .next_if(|next| next.kind == TokenKind::Punct('('))
+      .is_some()
+    {
+      loop {
+        if !args.is_empty() {
+          let token = tokens.next().unwrap();
+          match token.kind {
+            TokenKind::Punct(')') => break,
+            TokenKind::Punct(',') => {}
+            _ => return Err(unexpected(token, "`,` or `)`")),
+          }
+        }
+        let negative = tokens
+          .next_if(|next| next.kind == TokenKind::Punct('-'))
+          .is_some();
+        let token = tokens.next().unwrap();
+        let value = match &token.kind {
+          TokenKind::Number(value) => Value::Number(*value),
+          TokenKind::Ident(name) if name == "inf" => Value::Number(f64::INFINITY),
+          TokenKind::Ident(name) if name == "NaN" => Value::Number(f64::NAN),
+          TokenKind::Ident(name) if name == "true" && !negative => Value::Bool(true),
+          TokenKind::Ident(name) if name == "false" && !negative => Value::Bool(false),
+          _ => return Err(unexpected(token, "a number or a boolean")),
+        };
+        args.push(match value {
+          Value::Number(value) if negative => Value::Number(-value),
+          value => value,
+        });
+      }
+    }
+    symbols.push(Symbol {
+      name,
+      args,
+      line: token.line,
+      column: token.column,
+    });
+  }
+  Ok(symbols)
+}
+
+/// Writes symbols the way `parse_symbols` reads them back, one space apart.
+pub fn to_notation<T: Display>(symbols: &[T]) -> String {
+  symbols
+    .iter()
+    .map(|symbol| symbol.to_string())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
@@ -0,0 +1,507 @@
+use super::{
+  error::ParseError,
+  expr::{BinaryOp, Expr, Function, UnaryOp},
+  lexer::{tokenize, Token, TokenKind},
+  Grammar, Pattern, Production, Template,
+};
+use std::{collections::HashMap, sync::Arc};
+
+const MODULE_CHARS: &str = "+-&^\\/|$!';,.[]{}%~@?\"";
+
+pub(crate) struct Parser {
+  tokens: Vec<Token>,
+  position: usize,
+  defines: Vec<(String, Expr)>,
+  declared: HashMap<String, usize>,
+  scope: Vec<String>,
+}
+
+fn describe(kind: &TokenKind) -> String {
+  match kind {
+    TokenKind::Ident(name) => format!("`{}`", name),
+    TokenKind::Number(value) => format!("`{}`", value),
+    TokenKind::Punct(c) => format!("`{}`", c),
+    TokenKind::Newline => "end of line".to_string(),
+    TokenKind::Eof => "end of input".to_string(),
+  }
+}
+
+impl Parser {
+  pub fn new(source: &str) -> Result<Self, ParseError> {
+    Ok(Self {
+      tokens: tokenize(source)?,
+      position: 0,
+      defines: vec![],
+      declared: HashMap::new(),
+      scope: vec![],
+    })
+  }
+
+  fn peek(&self) -> &Token {
+    self.peek_at(0)
+  }
+
+  fn peek_at(&self, offset: usize) -> &Token {
+    let index = (self.position + offset).min(self.tokens.len() - 1);
+    &self.tokens[index]
+  }
+
+  fn advance(&mut self) -> Token {
+    let token = self.peek().clone();
+    if token.kind != TokenKind::Eof {
+      self.position += 1;
+    }
+    token
+  }
+
+  fn is_punct(&self, offset: usize, c: char) -> bool {
+    self.peek_at(offset).kind == TokenKind::Punct(c)
+  }
+
+  /// Two punctuation characters written without space in between.
+  fn is_op(&self, first: char, second: char) -> bool {
+    self.is_punct(0, first) && self.is_punct(1, second) && self.peek_at(0).touches(self.peek_at(1))
+  }
+
+  fn is_arrow(&self) -> bool {
+    self.is_op('-', '>')
+      || (self.is_op('-', '-') && self.is_punct(2, '>') && self.peek_at(1).touches(self.peek_at(2)))
+  }
+
+  fn is_end_of_statement(&self) -> bool {
+    matches!(self.peek().kind, TokenKind::Newline | TokenKind::Eof)
+  }
+
+  fn unexpected(&self, expected: &str) -> ParseError {
+    let token = self.peek();
+    token.error(format!(
+      "expected {}, found {}",
+      expected,
+      describe(&token.kind)
+    ))
+  }
+
+  fn expect_punct(&mut self, c: char) -> Result<Token, ParseError> {
+    if self.is_punct(0, c) {
+      Ok(self.advance())
+    } else {
+      Err(self.unexpected(&format!("`{}`", c)))
+    }
+  }
+
+  fn expect_ident(&mut self) -> Result<(String, Token), ParseError> {
+    match self.peek().kind.clone() {
+      TokenKind::Ident(name) => Ok((name, self.advance())),
+      _ => Err(self.unexpected("an identifier")),
+    }
+  }
+
+  fn skip_newlines(&mut self) {
+    while self.peek().kind == TokenKind::Newline {
+      self.advance();
+    }
+  }
+
+  fn is_keyword(&self, keyword: &str) -> bool {
+    matches!(&self.peek().kind, TokenKind::Ident(name) if name == keyword)
+  }
+
+  pub fn grammar(mut self) -> Result<Grammar, ParseError> {
+    let mut axiom = vec![];
+    let mut ignored = vec![];
+    let mut productions = vec![];
+    loop {
+      self.skip_newlines();
+      if self.peek().kind == TokenKind::Eof {
+        break;
+      }
+      if self.is_punct(0, '#') {
+        self.define()?;
+      } else if self.is_keyword("module") && matches!(self.peek_at(1).kind, TokenKind::Ident(_)) {
+        self.advance();
+        self.declarations()?;
+      } else if self.is_keyword("axiom") && self.is_punct(1, ':') {
+        self.advance();
+        self.advance();
+        axiom = self.modules()?;
+      } else if self.is_keyword("ignore") && self.is_punct(1, ':') {
+        self.advance();
+        self.advance();
+        while !self.is_end_of_statement() {
+          ignored.push(self.module_name()?.0);
+        }
+      } else {
+        productions.push(self.production()?);
+      }
+      if !self.is_end_of_statement() {
+        return Err(self.unexpected("end of line"));
+      }
+    }
+    Ok(Grammar {
+      defines: self.defines,
+      axiom,
+      ignored,
+      productions,
+    })
+  }
+
+  /// Module string whose parameters are constant expressions, as found in an
+  /// axiom.
+  pub fn modules(&mut self) -> Result<Vec<Template>, ParseError> {
+    let mut modules = vec![];
+    while !self.is_end_of_statement() && !self.is_punct(0, ':') {
+      modules.push(self.template()?);
+    }
+    Ok(modules)
+  }
+
+  fn define(&mut self) -> Result<(), ParseError> {
+    self.expect_punct('#')?;
+    match self.expect_ident()? {
+      (keyword, _) if keyword == "define" => {}
+      (_, token) => return Err(token.error("expected `define` after `#`")),
+    }
+    let (name, token) = self.expect_ident()?;
+    if self.defines.iter().any(|(defined, _)| *defined == name) {
+      return Err(token.error(format!("constant `{}` is already defined", name)));
+    }
+    let value = self.expr()?;
+    self.defines.push((name, value));
+    Ok(())
+  }
+
+  fn declarations(&mut self) -> Result<(), ParseError> {
+    loop {
+      let (name, token) = self.expect_ident()?;
+      let arity = if self.is_punct(0, '(') {
+        self.advance();
+        let mut arity = 0;
+        while !self.is_punct(0, ')') {
+          if arity > 0 {
+            self.expect_punct(',')?;
+          }
+          self.expect_ident()?;
+          arity += 1;
+        }
+        self.advance();
+        arity
+      } else {
+        0
+      };
+      if self.declared.insert(name.clone(), arity).is_some() {
+        return Err(token.error(format!("module `{}` is already declared", name)));
+      }
+      if !self.is_punct(0, ',') {
+        return Ok(());
+      }
+      self.advance();
+    }
+  }
+
+  /// Name of a module along with its declared arity, if it has one.
+  fn module_name(&mut self) -> Result<(Arc<str>, Option<usize>, Token), ParseError> {
+    match self.peek().kind.clone() {
+      TokenKind::Ident(name) => match self.declared.get(&name) {
+        Some(&arity) => Ok((name.into(), Some(arity), self.advance())),
+        None => Err(self.peek().error(format!("undeclared module `{}`", name))),
+      },
+      TokenKind::Punct(c) if MODULE_CHARS.contains(c) => {
+        Ok((c.to_string().into(), None, self.advance()))
+      }
+      _ => Err(self.unexpected("a module")),
+    }
+  }
+
+  fn check_arity(
+    name: &str,
+    declared: Option<usize>,
+    actual: usize,
+    token: &Token,
+  ) -> Result<(), ParseError> {
+    match declared {
+      Some(arity) if arity != actual => Err(token.error(format!(
+        "module `{}` takes {} parameter(s) but {} were given",
+        name, arity, actual
+      ))),
+      _ => Ok(()),
+    }
+  }
+
+  fn template(&mut self) -> Result<Template, ParseError> {
+    let (name, arity, token) = self.module_name()?;
+    let mut args = vec![];
+    if self.is_punct(0, '(') {
+      self.advance();
+      while !self.is_punct(0, ')') {
+        if !args.is_empty() {
+          self.expect_punct(',')?;
+        }
+        args.push(self.expr()?);
+      }
+      self.advance();
+    }
+    Self::check_arity(&name, arity, args.len(), &token)?;
+    Ok(Template { name, args })
+  }
+
+  fn pattern(&mut self) -> Result<Pattern, ParseError> {
+    let (name, arity, token) = self.module_name()?;
+    let mut params = 0;
+    if self.is_punct(0, '(') {
+      self.advance();
+      while !self.is_punct(0, ')') {
+        if params > 0 {
+          self.expect_punct(',')?;
+        }
+        let (param, token) = self.expect_ident()?;
+        if self.scope.contains(&param) {
+          return Err(token.error(format!("parameter `{}` is already bound", param)));
+        }
+        self.scope.push(param);
+        params += 1;
+      }
+      self.advance();
+    }
+    Self::check_arity(&name, arity, params, &token)?;
+    Ok(Pattern {
+      name,
+      arity: params,
+    })
+  }
+
+  fn production(&mut self) -> Result<Production, ParseError> {
+    self.scope.clear();
+    let mut left = None;
+    let mut predecessor = self.pattern()?;
+    if self.is_punct(0, '<') {
+      self.advance();
+      left = Some(predecessor);
+      predecessor = self.pattern()?;
+    }
+    let right = if self.is_punct(0, '>') {
+      self.advance();
+      Some(self.pattern()?)
+    } else {
+      None
+    };
+    let condition = if self.is_punct(0, ':') {
+      self.advance();
+      Some(self.expr()?)
+    } else {
+      None
+    };
+    if !self.is_arrow() {
+      return Err(self.unexpected("`->`"));
+    }
+    while !self.is_punct(0, '>') {
+      self.advance();
+    }
+    self.advance();
+    let successor = self.modules()?;
+    let weight = if self.is_punct(0, ':') {
+      self.advance();
+      Some(self.expr()?)
+    } else {
+      None
+    };
+    // Parameters are only bound within their production.
+    self.scope.clear();
+    Ok(Production {
+      left,
+      predecessor,
+      right,
+      condition,
+      successor,
+      weight,
+    })
+  }
+
+  fn expr(&mut self) -> Result<Expr, ParseError> {
+    let mut lhs = self.and()?;
+    while self.is_op('|', '|') {
+      self.advance();
+      self.advance();
+      lhs = Expr::Binary(BinaryOp::Or, Box::new(lhs), Box::new(self.and()?));
+    }
+    Ok(lhs)
+  }
+
+  fn and(&mut self) -> Result<Expr, ParseError> {
+    let mut lhs = self.comparison()?;
+    while self.is_op('&', '&') {
+      self.advance();
+      self.advance();
+      lhs = Expr::Binary(BinaryOp::And, Box::new(lhs), Box::new(self.comparison()?));
+    }
+    Ok(lhs)
+  }
+
+  fn comparison(&mut self) -> Result<Expr, ParseError> {
+    let mut lhs = self.additive()?;
+    loop {
+      let (op, width) = if self.is_op('<', '=') {
+        (BinaryOp::Le, 2)
+      } else if self.is_op('>', '=') {
+        (BinaryOp::Ge, 2)
+      } else if self.is_op('=', '=') {
+        (BinaryOp::Eq, 2)
+      } else if self.is_op('!', '=') {
+        (BinaryOp::Ne, 2)
+      } else if self.is_punct(0, '<') {
+        (BinaryOp::Lt, 1)
+      } else if self.is_punct(0, '>') {
+        (BinaryOp::Gt, 1)
+      } else {
+        return Ok(lhs);
+      };
+      for _ in 0..width {
+        self.advance();
+      }
+      lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.additive()?));
+    }
+  }
+
+  fn additive(&mut self) -> Result<Expr, ParseError> {
+    let mut lhs = self.multiplicative()?;
+    loop {
+      let op = if self.is_punct(0, '+') {
+        BinaryOp::Add
+      } else if self.is_punct(0, '-') && !self.is_arrow() {
+        BinaryOp::Sub
+      } else {
+        return Ok(lhs);
+      };
+      self.advance();
+      lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.multiplicative()?));
+    }
+  }
+
+  fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+    let mut lhs = self.unary()?;
+    loop {
+      let op = if self.is_punct(0, '*') {
+        BinaryOp::Mul
+      } else if self.is_punct(0, '/') {
+        BinaryOp::Div
+      } else if self.is_punct(0, '%') {
+        BinaryOp::Rem
+      } else {
+        return Ok(lhs);
+      };
+      self.advance();
+      lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+    }
+  }
+
+  fn unary(&mut self) -> Result<Expr, ParseError> {
+    if self.is_punct(0, '-') {
+      self.advance();
+      Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?)))
+    } else if self.is_punct(0, '!') {
+      self.advance();
+      Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+    } else {
+      self.power()
+    }
+  }
+
+  fn power(&mut self) -> Result<Expr, ParseError> {
+    let base = self.primary()?;
+    if self.is_punct(0, '^') {
+      self.advance();
+      Ok(Expr::Binary(
+        BinaryOp::Pow,
+        Box::new(base),
+        Box::new(self.unary()?),
+      ))
+    } else {
+      Ok(base)
+    }
+  }
+
+  fn primary(&mut self) -> Result<Expr, ParseError> {
+    match self.peek().kind.clone() {
+      TokenKind::Number(value) => {
+        self.advance();
+        Ok(Expr::Number(value))
+      }
+      TokenKind::Punct('(') => {
+        self.advance();
+        let expr = self.expr()?;
+        self.expect_punct(')')?;
+        Ok(expr)
+      }
+      TokenKind::Ident(name) => {
+        let token = self.advance();
+        if let Some(slot) = self.scope.iter().position(|param| *param == name) {
+          return Ok(Expr::Param(slot));
+        }
+        if let Some(slot) = self.defines.iter().position(|(define, _)| *define == name) {
+          return Ok(Expr::Constant(slot));
+        }
+        match name.as_str() {
+          "true" => return Ok(Expr::Number(1.0)),
+          "false" => return Ok(Expr::Number(0.0)),
+          _ => {}
+        }
+        let function = Function::from_name(&name)
+          .filter(|_| self.is_punct(0, '('))
+          .ok_or_else(|| token.error(format!("unknown identifier `{}`", name)))?;
+        self.advance();
+        let mut args = vec![];
+        while !self.is_punct(0, ')') {
+          if !args.is_empty() {
+            self.expect_punct(',')?;
+          }
+          args.push(self.expr()?);
+        }
+        self.advance();
+        if args.len() != function.arity() {
+          return Err(token.error(format!(
+            "`{}` takes {} argument(s) but {} were given",
+            name,
+            function.arity(),
+            args.len()
+          )));
+        }
+        Ok(Expr::Call(function, args))
+      }
+      _ => Err(self.unexpected("an expression")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn error(source: &str) -> ParseError {
+    match Parser::new(source).and_then(|parser| parser.grammar()) {
+      Ok(_) => panic!("parsed {:?}", source),
+      Err(error) => error,
+    }
+  }
+
+  #[test]
+  fn parameters_dont_leak_into_the_axiom() {
+    let error = error("module A(t)\nA(t) -> A(t+1)\naxiom: A(t)");
+    assert_eq!((error.line, error.column), (3, 10));
+    assert_eq!(error.message, "unknown identifier `t`");
+  }
+
+  #[test]
+  fn parameters_dont_leak_into_defines() {
+    let error = error("module A(t)\nA(t) -> A(t+1)\n#define K t");
+    assert_eq!((error.line, error.column), (3, 11));
+    assert_eq!(error.message, "unknown identifier `t`");
+  }
+
+  #[test]
+  fn parameters_are_bound_in_their_production() {
+    assert!(
+      Parser::new("module A(t)\n#define K 2\naxiom: A(K)\nA(t) -> A(t+K)")
+        .and_then(|parser| parser.grammar())
+        .is_ok()
+    );
+  }
+}
@@ -1,13 +1,22 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-use rand::{distributions::Standard, prelude::Distribution};
+use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng};
 
+use super::analysis::Kind;
+use super::context::{Bracket, Branching, Context};
+use super::derivation::Sink;
+use super::grammar::{
+    error::ParseError,
+    notation::{parse_symbols, Symbol, Value},
+};
 use crate::turtle::polygon::{TurtlePolygon, TurtlePolygonInterpretation};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Leaf {
     Vertex,
     Grow(f64, f64, Option<f64>),
+    /// Grows like `Grow` without a time, drawn between two vertices.
+    Internode(f64, f64),
     MainApex(f64, bool),
     SideApex(f64),
     AnglePos,
@@ -18,6 +27,17 @@ pub enum Leaf {
     Depile,
 }
 
+impl Leaf {
+    /// Time parameter of apices, e.g. as a payload for
+    /// `turtle::polygon::to_attributed_geom`.
+    pub fn time(&self) -> Option<f64> {
+        match *self {
+            Leaf::MainApex(time, _) | Leaf::SideApex(time) => Some(time),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct LeafParameters {
     main_apex_length: f64,
@@ -49,6 +69,13 @@ impl LeafParameters {
             potential_decrement,
         }
     }
+
+    /// How much side apices lose at every step, and so how soon they stop
+    /// growing.
+    pub fn with_potential_decrement(mut self, potential_decrement: f64) -> Self {
+        self.potential_decrement = potential_decrement;
+        self
+    }
 }
 
 impl Distribution<LeafParameters> for Standard {
@@ -85,56 +112,58 @@ pub const LEAF_AXIOM: &[Leaf; 12] = &[
     Leaf::Load,
 ];
 
-pub fn leaf_rule(input: Leaf, parameters: &LeafParameters) -> Vec<Leaf> {
-    match input {
-        Leaf::Vertex => vec![Leaf::Vertex],
-        Leaf::Grow(length, growth_rate, time) => match time {
-            Some(time) if time > 1.0 => vec![Leaf::Grow(
+pub fn leaf_rule(
+    input: Leaf,
+    context: &Context<Leaf>,
+    parameters: &LeafParameters,
+    rng: &mut StdRng,
+) -> Vec<Leaf> {
+    let mut successor = vec![];
+    leaf_rule_into(
+        &input,
+        context,
+        parameters,
+        rng,
+        &mut Sink::new(&mut successor),
+    );
+    successor
+}
+
+/// `leaf_rule` for `Derivation`, pushing successors instead of allocating
+/// them.
+pub fn leaf_rule_into(
+    input: &Leaf,
+    _context: &Context<Leaf>,
+    parameters: &LeafParameters,
+    _rng: &mut StdRng,
+    sink: &mut Sink<Leaf>,
+) {
+    match *input {
+        Leaf::Grow(length, growth_rate, time) => sink.push(match time {
+            Some(time) if time > 1.0 => Leaf::Grow(
                 length * growth_rate,
                 growth_rate,
                 Some(time - parameters.potential_decrement),
-            )],
-            None => vec![Leaf::Grow(length * growth_rate, growth_rate, None)],
-            _ => vec![Leaf::Grow(length, growth_rate, time)],
-        },
-        Leaf::MainApex(time, direction) => match direction {
-            true => vec![
-                Leaf::Vertex,
-                Leaf::Grow(
-                    parameters.main_apex_length,
-                    parameters.main_apex_growth_rate,
-                    None,
-                ),
-                Leaf::Vertex,
-                Leaf::Save,
-                Leaf::AnglePos,
-                Leaf::SideApex(time),
-                Leaf::Grow(
-                    parameters.notch_length,
-                    parameters.notch_growth_rate,
-                    Some(time),
-                ),
-                Leaf::Vertex,
-                Leaf::Depile,
-                Leaf::Load,
-                Leaf::Save,
-                Leaf::AnglePos,
-                Leaf::SideApex(time),
-                Leaf::Pile,
-                Leaf::Vertex,
-                Leaf::Load,
-                Leaf::MainApex(time + 1.0, direction),
-            ],
-            false => vec![
-                Leaf::Vertex,
-                Leaf::Grow(
+            ),
+            None => Leaf::Grow(length * growth_rate, growth_rate, None),
+            _ => Leaf::Grow(length, growth_rate, time),
+        }),
+        Leaf::Internode(length, growth_rate) => {
+            sink.push(Leaf::Internode(length * growth_rate, growth_rate))
+        }
+        Leaf::MainApex(time, direction) => {
+            let angle = if direction {
+                Leaf::AnglePos
+            } else {
+                Leaf::AngleNeg
+            };
+            sink.extend([
+                Leaf::Internode(
                     parameters.main_apex_length,
                     parameters.main_apex_growth_rate,
-                    None,
                 ),
-                Leaf::Vertex,
                 Leaf::Save,
-                Leaf::AngleNeg,
+                angle.clone(),
                 Leaf::SideApex(time),
                 Leaf::Grow(
                     parameters.notch_length,
@@ -145,34 +174,29 @@ pub fn leaf_rule(input: Leaf, parameters: &LeafParameters) -> Vec<Leaf> {
                 Leaf::Depile,
                 Leaf::Load,
                 Leaf::Save,
-                Leaf::AngleNeg,
+                angle,
                 Leaf::SideApex(time),
                 Leaf::Pile,
                 Leaf::Vertex,
                 Leaf::Load,
                 Leaf::MainApex(time + 1.0, direction),
-            ],
-        },
+            ])
+        }
         Leaf::SideApex(time) => {
             if time > 1.0 {
-                vec![
+                sink.extend([
                     Leaf::Grow(
                         parameters.side_apex_length,
                         parameters.side_apex_growth_rate,
                         None,
                     ),
                     Leaf::SideApex(time - parameters.potential_decrement),
-                ]
+                ])
             } else {
-                vec![Leaf::SideApex(time)]
+                sink.push(Leaf::SideApex(time))
             }
         }
-        Leaf::AnglePos => vec![Leaf::AnglePos],
-        Leaf::AngleNeg => vec![Leaf::AngleNeg],
-        Leaf::Save => vec![Leaf::Save],
-        Leaf::Load => vec![Leaf::Load],
-        Leaf::Pile => vec![Leaf::Pile],
-        Leaf::Depile => vec![Leaf::Depile],
+        _ => sink.push(input.clone()),
     }
 }
 
@@ -184,6 +208,7 @@ impl Display for Leaf {
                 Some(time) => write!(f, "G({}, {}, {})", length, growth_rate, time),
                 None => write!(f, "G({}, {})", length, growth_rate),
             },
+            Leaf::Internode(length, growth_rate) => write!(f, "I({}, {})", length, growth_rate),
             Leaf::MainApex(time, direction) => write!(f, "A({}, {})", time, direction),
             Leaf::SideApex(time) => write!(f, "B({})", time),
             Leaf::AnglePos => write!(f, "+"),
@@ -196,11 +221,85 @@ impl Display for Leaf {
     }
 }
 
+impl TryFrom<&Symbol> for Leaf {
+    type Error = ParseError;
+
+    fn try_from(symbol: &Symbol) -> Result<Self, Self::Error> {
+        use Value::{Bool, Number};
+        Ok(match (symbol.name.as_str(), symbol.args.as_slice()) {
+            (".", []) => Leaf::Vertex,
+            ("G", [Number(length), Number(growth_rate)]) => Leaf::Grow(*length, *growth_rate, None),
+            ("G", [Number(length), Number(growth_rate), Number(time)]) => {
+                Leaf::Grow(*length, *growth_rate, Some(*time))
+            }
+            ("I", [Number(length), Number(growth_rate)]) => Leaf::Internode(*length, *growth_rate),
+            ("A", [Number(time), Bool(direction)]) => Leaf::MainApex(*time, *direction),
+            ("B", [Number(time)]) => Leaf::SideApex(*time),
+            ("+", []) => Leaf::AnglePos,
+            ("-", []) => Leaf::AngleNeg,
+            ("[", []) => Leaf::Save,
+            ("]", []) => Leaf::Load,
+            ("{", []) => Leaf::Pile,
+            ("}", []) => Leaf::Depile,
+            ("." | "G" | "I" | "A" | "B" | "+" | "-" | "[" | "]" | "{" | "}", _) => {
+                return Err(symbol.error(format!("invalid parameters for `{}`", symbol.name)))
+            }
+            (name, _) => return Err(symbol.error(format!("unknown leaf symbol `{}`", name))),
+        })
+    }
+}
+
+impl FromStr for Leaf {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_symbols(s)?.as_slice() {
+            [symbol] => Leaf::try_from(symbol),
+            [] => Err(ParseError::new(1, 1, "expected a leaf symbol")),
+            [_, extra, ..] => Err(extra.error("expected a single leaf symbol")),
+        }
+    }
+}
+
+/// Reads back a derived string, such as one written by `to_notation`.
+pub fn parse_leaves(source: &str) -> Result<Vec<Leaf>, ParseError> {
+    parse_symbols(source)?.iter().map(Leaf::try_from).collect()
+}
+
+impl Branching for Leaf {
+    fn bracket(&self) -> Option<Bracket> {
+        match self {
+            Leaf::Save => Some(Bracket::Open),
+            Leaf::Load => Some(Bracket::Close),
+            _ => None,
+        }
+    }
+}
+
+impl Kind for Leaf {
+    fn kind(&self) -> &str {
+        match self {
+            Leaf::Vertex => "Vertex",
+            Leaf::Grow(..) => "Grow",
+            Leaf::Internode(..) => "Internode",
+            Leaf::MainApex(..) => "MainApex",
+            Leaf::SideApex(..) => "SideApex",
+            Leaf::AnglePos => "AnglePos",
+            Leaf::AngleNeg => "AngleNeg",
+            Leaf::Save => "Save",
+            Leaf::Load => "Load",
+            Leaf::Pile => "Pile",
+            Leaf::Depile => "Depile",
+        }
+    }
+}
+
 impl TurtlePolygonInterpretation for Leaf {
     fn to_turtle(&self) -> TurtlePolygon {
         match *self {
             Leaf::Vertex => TurtlePolygon::Vertex,
             Leaf::Grow(length, _, _) => TurtlePolygon::Forward(length),
+            Leaf::Internode(length, _) => TurtlePolygon::Forward(length),
             Leaf::MainApex(_, _) => TurtlePolygon::None,
             Leaf::SideApex(_) => TurtlePolygon::None,
             Leaf::AnglePos => TurtlePolygon::Left,
@@ -211,4 +310,41 @@ impl TurtlePolygonInterpretation for Leaf {
             Leaf::Depile => TurtlePolygon::ClosePolygon,
         }
     }
+
+    fn interpret(&self, commands: &mut Vec<TurtlePolygon>) {
+        match *self {
+            Leaf::Internode(length, _) => commands.extend([
+                TurtlePolygon::Vertex,
+                TurtlePolygon::Forward(length),
+                TurtlePolygon::Vertex,
+            ]),
+            _ => commands.push(self.to_turtle()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::{grammar::notation::to_notation, LSystem};
+    use rand::SeedableRng;
+
+    #[test]
+    fn notation_reads_back() {
+        let parameters: LeafParameters = Standard.sample(&mut StdRng::seed_from_u64(3));
+        let leaves = LSystem::new(LEAF_AXIOM.to_vec(), leaf_rule, parameters)
+            .nth(6)
+            .unwrap();
+        assert_eq!(parse_leaves(&to_notation(&leaves)).unwrap(), leaves);
+    }
+
+    #[test]
+    fn errors_point_at_the_symbol() {
+        let error = parse_leaves("[ A(0, true) ]\n  { G(1) }").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 5));
+        assert_eq!(error.message, "invalid parameters for `G`");
+        let error = parse_leaves(". X").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 3));
+        assert_eq!(error.message, "unknown leaf symbol `X`");
+    }
 }
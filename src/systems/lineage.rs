@@ -0,0 +1,319 @@
+//! Which symbol produced which, across the steps of a derivation.
+
+use std::{iter::repeat_n, mem::discriminant, ops::Range};
+
+/// No successor shares its parent's variant.
+const NO_HEIR: u32 = u32::MAX;
+
+/// One step of a `Lineage`, run-length encoded: the successors of every
+/// symbol follow each other, so where each run ends is enough.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Step {
+  /// End of each parent's successors in the next generation.
+  ends: Vec<usize>,
+  /// Offset, among each parent's successors, of its heir.
+  heirs: Vec<u32>,
+}
+
+impl Step {
+  pub(super) fn clear(&mut self) {
+    self.ends.clear();
+    self.heirs.clear();
+  }
+
+  /// Records the successors of `parent`, the symbols of `written` from
+  /// `start` on.
+  pub(super) fn record<T>(&mut self, parent: &T, written: &[T], start: usize) {
+    let heir = written[start..]
+      .iter()
+      .position(|successor| discriminant(successor) == discriminant(parent))
+      .map_or(NO_HEIR, |offset| offset as u32);
+    self.ends.push(written.len());
+    self.heirs.push(heir);
+  }
+
+  /// Appends the runs of `other`, whose successors were written after
+  /// `offset` others.
+  #[cfg(feature = "rayon")]
+  pub(super) fn append(&mut self, other: &mut Step, offset: usize) {
+    self
+      .ends
+      .extend(other.ends.drain(..).map(|end| end + offset));
+    self.heirs.append(&mut other.heirs);
+  }
+
+  fn parent(&self, index: usize) -> Option<usize> {
+    let parent = self.ends.partition_point(|end| *end <= index);
+    (parent < self.ends.len()).then_some(parent)
+  }
+
+  fn children(&self, parent: usize) -> Option<Range<usize>> {
+    let end = *self.ends.get(parent)?;
+    let start = parent
+      .checked_sub(1)
+      .map_or(0, |previous| self.ends[previous]);
+    Some(start..end)
+  }
+
+  fn is_heir(&self, parent: usize, index: usize) -> bool {
+    let heir = self.heirs[parent];
+    heir != NO_HEIR && self.children(parent).unwrap().start + heir as usize == index
+  }
+}
+
+/// Symbol named by where it first appeared. Symbols keep it for as long as
+/// they're rewritten into their heir, the first of their successors sharing
+/// their variant: a `Vertex` left as is, an apex growing older.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolId {
+  pub generation: usize,
+  pub index: usize,
+}
+
+/// Parents of every symbol derived, recorded by `LSystem::with_lineage` and
+/// `Derivation::with_lineage`. Symbols are located by generation, the axiom
+/// being generation 0, and index in their string, such as
+/// `VertexAttributes::index` for what the turtle drew from the last one.
+/// Recording may start after a few steps, in which case symbols of earlier
+/// generations are unknown.
+#[derive(Clone, Debug, Default)]
+pub struct Lineage {
+  start: usize,
+  steps: Vec<Step>,
+  pending: Step,
+}
+
+impl Lineage {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Lineage whose recording starts from `generation`.
+  pub(super) fn starting_at(generation: usize) -> Self {
+    Self {
+      start: generation,
+      ..Self::default()
+    }
+  }
+
+  /// First generation recorded.
+  pub fn start(&self) -> usize {
+    self.start
+  }
+
+  /// Last generation recorded.
+  pub fn generation(&self) -> usize {
+    self.start + self.steps.len()
+  }
+
+  /// Step deriving `generation + 1` from `generation`, if recorded.
+  fn step(&self, generation: usize) -> Option<&Step> {
+    self.steps.get(generation.checked_sub(self.start)?)
+  }
+
+  /// Runs of the step being derived.
+  pub(super) fn pending(&mut self) -> &mut Step {
+    &mut self.pending
+  }
+
+  /// Keeps the step being derived, once it is complete.
+  pub(super) fn commit(&mut self) {
+    self.steps.push(std::mem::take(&mut self.pending));
+  }
+
+  /// Index in `generation - 1` of the symbol that produced symbol `index`
+  /// of `generation`.
+  pub fn parent(&self, generation: usize, index: usize) -> Option<usize> {
+    self.step(generation.checked_sub(1)?)?.parent(index)
+  }
+
+  /// Indices in `generation + 1` of the successors of symbol `index` of
+  /// `generation`.
+  pub fn children(&self, generation: usize, index: usize) -> Option<Range<usize>> {
+    self.step(generation)?.children(index)
+  }
+
+  /// Parent of every symbol of `generation`, one after the other.
+  pub fn parents(&self, generation: usize) -> Option<Vec<usize>> {
+    let step = self.step(generation.checked_sub(1)?)?;
+    Some(
+      (0..step.ends.len())
+        .flat_map(|parent| repeat_n(parent, step.children(parent).unwrap().len()))
+        .collect(),
+    )
+  }
+
+  /// Index in generation `ancestor` of the symbol that symbol `index` of
+  /// `generation` descends from.
+  pub fn ancestor(&self, generation: usize, index: usize, ancestor: usize) -> Option<usize> {
+    if ancestor > generation || generation > self.generation() {
+      return None;
+    }
+    (ancestor..generation)
+      .rev()
+      .try_fold(index, |index, parent| self.step(parent)?.parent(index))
+  }
+
+  /// Where symbol `index` of `generation` first appeared, which holds across
+  /// steps for as long as it is rewritten into its heir. Symbols that were
+  /// already there when recording started appeared there as far as this
+  /// lineage knows.
+  pub fn identity(&self, generation: usize, index: usize) -> Option<SymbolId> {
+    if generation < self.start || generation > self.generation() {
+      return None;
+    }
+    let mut id = SymbolId { generation, index };
+    while id.generation > self.start {
+      let step = &self.steps[id.generation - 1 - self.start];
+      let parent = step.parent(id.index)?;
+      if !step.is_heir(parent, id.index) {
+        break;
+      }
+      id = SymbolId {
+        generation: id.generation - 1,
+        index: parent,
+      };
+    }
+    Some(id)
+  }
+
+  /// Identity of the symbol that spawned symbol `index` of `generation`: the
+  /// parent it had when it first appeared. Symbols of the axiom have none,
+  /// nor those of the first generation recorded.
+  pub fn spawner(&self, generation: usize, index: usize) -> Option<SymbolId> {
+    let origin = self.identity(generation, index)?;
+    let parent = self.parent(origin.generation, origin.index)?;
+    self.identity(origin.generation - 1, parent)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::systems::{
+    context::{Bracket, Branching, Context},
+    derivation::{Derivation, Sink},
+    LSystem,
+  };
+  use rand::rngs::StdRng;
+
+  #[derive(Clone, Debug, PartialEq)]
+  enum Symbol {
+    A,
+    B,
+    C,
+  }
+
+  impl Branching for Symbol {
+    fn bracket(&self) -> Option<Bracket> {
+      None
+    }
+  }
+
+  /// `A` grows a `B` before itself and a `C` after, `C` withers.
+  fn rule(symbol: &Symbol, _: &Context<Symbol>, _: &(), _: &mut StdRng, sink: &mut Sink<Symbol>) {
+    match symbol {
+      Symbol::A => sink.extend([Symbol::B, Symbol::A, Symbol::C]),
+      Symbol::B => sink.push(Symbol::B),
+      Symbol::C => {}
+    }
+  }
+
+  /// `rule` for `LSystem`.
+  fn successor(symbol: Symbol, context: &Context<Symbol>, _: &(), rng: &mut StdRng) -> Vec<Symbol> {
+    let mut successor = vec![];
+    rule(&symbol, context, &(), rng, &mut Sink::new(&mut successor));
+    successor
+  }
+
+  /// Generations `A C`, `B A C` and `B B A C`, the next one being recorded
+  /// too.
+  fn lineage() -> Lineage {
+    let mut l_system = LSystem::new(vec![Symbol::A, Symbol::C], successor, ()).with_lineage();
+    assert_eq!(
+      l_system.nth(2).unwrap(),
+      [Symbol::B, Symbol::B, Symbol::A, Symbol::C]
+    );
+    l_system.lineage().unwrap().clone()
+  }
+
+  #[test]
+  fn parents_and_children() {
+    let lineage = lineage();
+    assert_eq!(lineage.generation(), 3);
+    assert_eq!(lineage.children(0, 0), Some(0..3));
+    assert_eq!(lineage.children(0, 1), Some(3..3));
+    assert_eq!(lineage.parent(1, 2), Some(0));
+    assert_eq!(lineage.parent(2, 0), Some(0));
+    assert_eq!(lineage.parent(2, 3), Some(1));
+    assert_eq!(lineage.parent(0, 0), None);
+    assert_eq!(lineage.parents(2), Some(vec![0, 1, 1, 1]));
+    assert_eq!(lineage.ancestor(2, 3, 0), Some(0));
+  }
+
+  #[test]
+  fn identities_follow_heirs() {
+    let lineage = lineage();
+    let id = |generation, index| SymbolId { generation, index };
+    assert_eq!(lineage.identity(2, 2), Some(id(0, 0)));
+    assert_eq!(lineage.identity(2, 0), Some(id(1, 0)));
+    assert_eq!(lineage.identity(2, 1), Some(id(2, 1)));
+    assert_eq!(lineage.identity(2, 3), Some(id(2, 3)));
+    assert_eq!(lineage.spawner(2, 0), Some(id(0, 0)));
+    assert_eq!(lineage.spawner(2, 1), Some(id(0, 0)));
+    assert_eq!(lineage.spawner(2, 3), Some(id(0, 0)));
+    assert_eq!(lineage.spawner(2, 2), None);
+    assert_eq!(lineage.identity(4, 0), None);
+  }
+
+  #[test]
+  fn engines_record_the_same_lineage() {
+    let expected = lineage();
+    let mut derivation = Derivation::new(vec![Symbol::A, Symbol::C], rule, ()).with_lineage();
+    derivation.advance(2);
+    let recorded = derivation.lineage().unwrap();
+    for generation in 1..=2 {
+      assert_eq!(recorded.parents(generation), expected.parents(generation));
+    }
+  }
+
+  #[test]
+  fn generations_count_from_the_axiom_when_recording_starts_late() {
+    let mut derivation = Derivation::new(vec![Symbol::A, Symbol::C], rule, ());
+    derivation.step();
+    let mut derivation = derivation.with_lineage();
+    derivation.step();
+    let recorded = derivation.lineage().unwrap();
+    let expected = lineage();
+    assert_eq!((recorded.start(), recorded.generation()), (1, 2));
+    assert_eq!(recorded.parents(2), expected.parents(2));
+    assert_eq!(recorded.parents(1), None);
+    assert_eq!(recorded.parent(2, 3), Some(1));
+    assert_eq!(recorded.ancestor(2, 3, 1), Some(1));
+    assert_eq!(recorded.ancestor(2, 3, 0), None);
+    assert_eq!(
+      recorded.identity(2, 2),
+      Some(SymbolId {
+        generation: 1,
+        index: 1
+      })
+    );
+    assert_eq!(recorded.identity(0, 0), None);
+    assert_eq!(recorded.spawner(2, 2), None);
+    assert_eq!(
+      recorded.spawner(2, 1),
+      Some(SymbolId {
+        generation: 1,
+        index: 1
+      })
+    );
+
+    let mut l_system = LSystem::new(vec![Symbol::A, Symbol::C], successor, ());
+    l_system.next();
+    let mut l_system = l_system.with_lineage();
+    l_system.next();
+    let recorded = l_system.lineage().unwrap();
+    assert_eq!((recorded.start(), recorded.generation()), (1, 2));
+    assert_eq!(recorded.parents(2), expected.parents(2));
+  }
+}
@@ -1,41 +1,174 @@
+pub mod analysis;
+pub mod context;
+pub mod control;
+pub mod derivation;
+pub mod grammar;
 pub mod leaf;
+pub mod lineage;
+pub mod stochastic;
+
+use context::{Branching, Context};
+use lineage::Lineage;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashSet, mem::Discriminant};
+
+/// Symbols rewritten with the same generator within a step. Every block of
+/// them gets its own, seeded from the step's seed and where the block lies,
+/// so that what symbols derive doesn't depend on the order or the thread
+/// they're rewritten in, only on blocks being rewritten one symbol after
+/// the other.
+pub const RNG_BLOCK: usize = 64;
+
+/// Generator of the block of symbols starting at `index`.
+pub(crate) fn block_rng(seed: u64, index: usize) -> StdRng {
+  StdRng::seed_from_u64(seed.wrapping_add((index / RNG_BLOCK) as u64))
+}
 
 pub struct LSystem<T, F, P>
 where
-  F: FnMut(T, &P) -> Vec<T>,
+  F: FnMut(T, &Context<T>, &P, &mut StdRng) -> Vec<T>,
 {
   axiom: Vec<T>,
   rules: F,
   constants: P,
+  ignored: HashSet<Discriminant<T>>,
+  rng: StdRng,
+  generation: usize,
+  lineage: Option<Lineage>,
 }
 
 impl<T, F, P> LSystem<T, F, P>
 where
-  F: FnMut(T, &P) -> Vec<T>,
+  F: FnMut(T, &Context<T>, &P, &mut StdRng) -> Vec<T>,
 {
   pub fn new(axiom: Vec<T>, rules: F, constants: P) -> LSystem<T, F, P> {
     LSystem {
       axiom,
       rules,
       constants,
+      ignored: HashSet::new(),
+      rng: StdRng::seed_from_u64(0),
+      generation: 0,
+      lineage: None,
     }
   }
+
+  /// Reseeds the generator drawing the seed of every step, from which each
+  /// block of `RNG_BLOCK` symbols gets a generator of its own. Two systems
+  /// built from the same axiom, rules and seed derive the same strings.
+  pub fn with_seed(mut self, seed: u64) -> Self {
+    self.rng = StdRng::seed_from_u64(seed);
+    self
+  }
+
+  /// Makes every symbol sharing a variant with one of `symbols` transparent
+  /// to context matching, whatever its parameters.
+  pub fn with_ignored(mut self, symbols: &[T]) -> Self {
+    self
+      .ignored
+      .extend(symbols.iter().map(std::mem::discriminant));
+    self
+  }
+
+  /// Records the parent of every symbol derived from now on. Generations are
+  /// still numbered from the axiom, even if some were derived already.
+  pub fn with_lineage(mut self) -> Self {
+    self.lineage = Some(Lineage::starting_at(self.generation));
+    self
+  }
+
+  /// Parents of the symbols derived so far, the string the next call to
+  /// `next` returns included, if recorded.
+  pub fn lineage(&self) -> Option<&Lineage> {
+    self.lineage.as_ref()
+  }
 }
 
 impl<T, F, P> Iterator for LSystem<T, F, P>
 where
-  T: Clone,
-  F: FnMut(T, &P) -> Vec<T>,
+  T: Clone + Branching,
+  F: FnMut(T, &Context<T>, &P, &mut StdRng) -> Vec<T>,
 {
   type Item = Vec<T>;
 
   fn next(&mut self) -> Option<Vec<T>> {
-    let result = self.axiom.clone();
-    let mut new_axiom = Vec::new();
-    for element in self.axiom.drain(..) {
-      new_axiom.extend((self.rules)(element, &self.constants).into_iter());
+    let result = std::mem::take(&mut self.axiom);
+    let mut new_axiom = Vec::with_capacity(result.len());
+    let seed = self.rng.gen();
+    let mut rng = block_rng(seed, 0);
+    for (index, element) in result.iter().enumerate() {
+      if index % RNG_BLOCK == 0 {
+        rng = block_rng(seed, index);
+      }
+      let context = Context::new(&result, index, &self.ignored);
+      let start = new_axiom.len();
+      new_axiom.extend((self.rules)(
+        element.clone(),
+        &context,
+        &self.constants,
+        &mut rng,
+      ));
+      if let Some(lineage) = self.lineage.as_mut() {
+        lineage.pending().record(element, &new_axiom, start);
+      }
+    }
+    if let Some(lineage) = self.lineage.as_mut() {
+      lineage.commit();
     }
     self.axiom = new_axiom;
+    self.generation += 1;
     Some(result)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use context::Bracket;
+  use stochastic::pick;
+
+  #[derive(Clone, Debug, PartialEq)]
+  enum Symbol {
+    Apex,
+    Segment,
+    Open,
+    Close,
+  }
+
+  impl Branching for Symbol {
+    fn bracket(&self) -> Option<Bracket> {
+      match self {
+        Symbol::Open => Some(Bracket::Open),
+        Symbol::Close => Some(Bracket::Close),
+        _ => None,
+      }
+    }
+  }
+
+  fn derive(seed: u64) -> Vec<Symbol> {
+    use Symbol::*;
+    LSystem::new(
+      vec![Apex],
+      |symbol, _: &Context<Symbol>, _: &(), rng| match symbol {
+        Apex => pick(
+          rng,
+          vec![
+            (1.0, vec![Segment, Apex]),
+            (1.0, vec![Segment, Open, Apex, Close, Apex]),
+          ],
+        ),
+        symbol => vec![symbol],
+      },
+      (),
+    )
+    .with_seed(seed)
+    .nth(8)
+    .unwrap()
+  }
+
+  #[test]
+  fn seeds_derive_reproducibly() {
+    assert_eq!(derive(1), derive(1));
+    assert_ne!(derive(1), derive(2));
+  }
+}
@@ -0,0 +1,27 @@
+use rand::Rng;
+
+/// Picks one of several weighted successors, for stochastic productions.
+/// Weights are relative and need not sum to one; `successors` must not be
+/// empty.
+pub fn pick<T, R: Rng + ?Sized>(rng: &mut R, mut successors: Vec<(f64, Vec<T>)>) -> Vec<T> {
+  let weights = successors
+    .iter()
+    .map(|(weight, _)| *weight)
+    .collect::<Vec<_>>();
+  let index = pick_index(rng, &weights);
+  successors.swap_remove(index).1
+}
+
+/// Index of the weight drawn, each being chosen with a probability
+/// proportional to its value. `weights` must not be empty.
+pub fn pick_index<R: Rng + ?Sized>(rng: &mut R, weights: &[f64]) -> usize {
+  let total: f64 = weights.iter().sum();
+  let mut threshold = rng.gen::<f64>() * total;
+  for (index, weight) in weights.iter().enumerate() {
+    if threshold < *weight {
+      return index;
+    }
+    threshold -= weight;
+  }
+  weights.len() - 1
+}
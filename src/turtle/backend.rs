@@ -0,0 +1,87 @@
+use super::{
+  error::{TurtleError, TurtleErrorKind},
+  polygon::{Params, Turtle, TurtlePolygon, TurtlePolygonInterpretation},
+};
+
+/// Consumer of a turtle walk. Every callback gets the index of the symbol
+/// being interpreted and the turtle as it is once the command is applied;
+/// all of them do nothing by default.
+pub trait TurtleBackend<T> {
+  /// Called once per symbol, before the commands it stands for.
+  fn symbol(&mut self, _symbol: &T, _index: usize) {}
+  /// Moved without drawing.
+  fn move_to(&mut self, _turtle: &Turtle, _index: usize) {}
+  /// Moved from `from` while drawing.
+  fn line_to(&mut self, _from: &Turtle, _turtle: &Turtle, _index: usize) {}
+  fn vertex(&mut self, _turtle: &Turtle, _index: usize) {}
+  fn begin_polygon(&mut self, _turtle: &Turtle, _index: usize) {}
+  fn end_polygon(&mut self, _turtle: &Turtle, _index: usize) {}
+  /// `turtle` has just been saved.
+  fn push(&mut self, _turtle: &Turtle, _index: usize) {}
+  /// `turtle` has just been restored.
+  fn pop(&mut self, _turtle: &Turtle, _index: usize) {}
+}
+
+impl<T> TurtleBackend<T> for () {}
+
+/// Walks the turtle through `symbols`, reporting what it does to `backend`.
+/// Brackets and polygons are checked on the way, so callbacks always come
+/// balanced.
+pub fn walk<T, B>(symbols: &[T], params: &Params, backend: &mut B) -> Result<(), TurtleError>
+where
+  T: TurtlePolygonInterpretation,
+  B: TurtleBackend<T>,
+{
+  let mut turtle = params.turtle();
+  let mut states = vec![];
+  let mut polygons = vec![];
+  let mut expansion = vec![];
+  for (index, symbol) in symbols.iter().enumerate() {
+    backend.symbol(symbol, index);
+    symbol.interpret(&mut expansion);
+    for command in expansion.drain(..) {
+      match command {
+        TurtlePolygon::Vertex => backend.vertex(&turtle, index),
+        TurtlePolygon::Forward(length) => {
+          let from = turtle;
+          turtle.forward(length, params.step);
+          turtle.bend(params.tropism);
+          backend.line_to(&from, &turtle, index);
+        }
+        TurtlePolygon::Move(length) => {
+          turtle.forward(length, params.step);
+          backend.move_to(&turtle, index);
+        }
+        TurtlePolygon::Push => {
+          states.push((turtle, index));
+          backend.push(&turtle, index);
+        }
+        TurtlePolygon::Pop => {
+          (turtle, _) = states
+            .pop()
+            .ok_or(TurtleError::new(index, TurtleErrorKind::UnmatchedPop))?;
+          backend.pop(&turtle, index);
+        }
+        TurtlePolygon::NewPolygon => {
+          polygons.push(index);
+          backend.begin_polygon(&turtle, index);
+        }
+        TurtlePolygon::ClosePolygon => {
+          polygons.pop().ok_or(TurtleError::new(
+            index,
+            TurtleErrorKind::UnmatchedClosePolygon,
+          ))?;
+          backend.end_polygon(&turtle, index);
+        }
+        command => turtle.steer(&command, params),
+      }
+    }
+  }
+  if let Some((_, index)) = states.last() {
+    return Err(TurtleError::new(*index, TurtleErrorKind::UnclosedPush));
+  }
+  if let Some(index) = polygons.last() {
+    return Err(TurtleError::new(*index, TurtleErrorKind::UnclosedPolygon));
+  }
+  Ok(())
+}
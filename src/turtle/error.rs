@@ -0,0 +1,41 @@
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TurtleErrorKind {
+  /// `Pop` without a matching `Push`.
+  UnmatchedPop,
+  /// `ClosePolygon` without a matching `NewPolygon`.
+  UnmatchedClosePolygon,
+  /// `Push` still open at the end of the string.
+  UnclosedPush,
+  /// `NewPolygon` still open at the end of the string.
+  UnclosedPolygon,
+}
+
+/// Malformed command string, `index` being the position of the offending
+/// command in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TurtleError {
+  pub index: usize,
+  pub kind: TurtleErrorKind,
+}
+
+impl TurtleError {
+  pub fn new(index: usize, kind: TurtleErrorKind) -> Self {
+    Self { index, kind }
+  }
+}
+
+impl Display for TurtleError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let description = match self.kind {
+      TurtleErrorKind::UnmatchedPop => "pop without a matching push",
+      TurtleErrorKind::UnmatchedClosePolygon => "polygon closed without being opened",
+      TurtleErrorKind::UnclosedPush => "push never popped",
+      TurtleErrorKind::UnclosedPolygon => "polygon never closed",
+    };
+    write!(f, "command {}: {}", self.index, description)
+  }
+}
+
+impl std::error::Error for TurtleError {}
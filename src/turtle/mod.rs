@@ -1 +1,5 @@
+pub mod backend;
+pub mod error;
 pub mod polygon;
+pub mod skeleton;
+pub mod tube;
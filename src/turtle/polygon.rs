@@ -1,15 +1,42 @@
-use crate::utils::geometry::{WorldPoint, WorldRotation, WorldVector};
-use euclid::Angle;
-use std::{collections::VecDeque, f64::consts::FRAC_PI_4, fmt::Debug};
+use super::{
+    backend::{walk, TurtleBackend},
+    error::TurtleError,
+};
+use crate::utils::geometry::{WorldPoint, WorldVector};
+use std::fmt::Debug;
 
 pub trait TurtlePolygonInterpretation {
     fn to_turtle(&self) -> TurtlePolygon;
+
+    /// Appends the commands the symbol stands for, so that detail only
+    /// needed to draw can stay out of the derived string. Defaults to the
+    /// single command given by `to_turtle`.
+    fn interpret(&self, commands: &mut Vec<TurtlePolygon>) {
+        commands.push(self.to_turtle());
+    }
 }
 pub enum TurtlePolygon {
     Vertex,
     Forward(f64),
+    /// Moves forward without drawing.
+    Move(f64),
     Left,
     Right,
+    PitchDown,
+    PitchUp,
+    RollLeft,
+    RollRight,
+    TurnAround,
+    RollHorizontal,
+    SetWidth(f64),
+    DecrementWidth,
+    SetColor(usize),
+    NextColor,
+    IncrementAngle,
+    DecrementAngle,
+    ScaleStep,
+    /// Overrides the tropism susceptibility for the following steps.
+    SetSusceptibility(f64),
     Push,
     Pop,
     NewPolygon,
@@ -17,51 +44,493 @@ pub enum TurtlePolygon {
     None,
 }
 
+/// By default the turtle starts at the origin heading towards +y, its left
+/// towards -x and its up out of the screen towards +z, and `Forward(length)`
+/// moves it by exactly `length` along its heading.
 pub struct Params {
     pub angle: f64,
+    pub pitch_angle: f64,
+    pub roll_angle: f64,
+    /// Direction opposite to gravity, which `RollHorizontal` keeps the left
+    /// vector perpendicular to.
+    pub vertical: WorldVector,
+    pub position: WorldPoint,
+    pub heading: WorldVector,
+    pub left: WorldVector,
+    pub up: WorldVector,
+    /// Unit direction of `Forward`, in the turtle's (heading, left, up)
+    /// coordinates.
+    pub step: WorldVector,
+    /// Initial line width, 1 by default.
+    pub width: f64,
+    /// Initial color index, 0 by default.
+    pub color: usize,
+    /// Subtracted from the width by `DecrementWidth`, 0.1 by default.
+    pub width_decrement: f64,
+    /// Added to every rotation angle by `IncrementAngle`, 0.1 radians by
+    /// default.
+    pub angle_increment: f64,
+    /// Multiplies the length of later steps on `ScaleStep`, 0.9 by default.
+    pub step_factor: f64,
+    /// Direction the heading bends towards after every `Forward`, such as
+    /// gravity or light. Null by default.
+    pub tropism: WorldVector,
+    /// Initial susceptibility to `tropism`, 0 by default.
+    pub susceptibility: f64,
 }
 
 impl Params {
     pub fn new(angle: f64) -> Self {
-        Self { angle }
+        Self {
+            angle,
+            pitch_angle: angle,
+            roll_angle: angle,
+            vertical: WorldVector::new(0.0, 0.0, 1.0),
+            position: WorldPoint::origin(),
+            heading: WorldVector::new(0.0, 1.0, 0.0),
+            left: WorldVector::new(-1.0, 0.0, 0.0),
+            up: WorldVector::new(0.0, 0.0, 1.0),
+            step: WorldVector::new(1.0, 0.0, 0.0),
+            width: 1.0,
+            color: 0,
+            width_decrement: 0.1,
+            angle_increment: 0.1,
+            step_factor: 0.9,
+            tropism: WorldVector::zero(),
+            susceptibility: 0.0,
+        }
+    }
+
+    pub fn with_position(mut self, position: WorldPoint) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets the initial frame from a heading and a left vector, which is made
+    /// orthogonal to the heading. Up completes the right-handed frame.
+    ///
+    /// Panics if either vector is null or if they are parallel.
+    pub fn with_frame(mut self, heading: WorldVector, left: WorldVector) -> Self {
+        self.heading = heading.try_normalize().expect("heading can't be null");
+        let left = left.try_normalize().expect("left can't be null");
+        let left = left - self.heading * self.heading.dot(left);
+        assert!(
+            left.square_length() > f64::EPSILON,
+            "left can't be parallel to the heading"
+        );
+        self.left = left.normalize();
+        self.up = self.heading.cross(self.left);
+        self
+    }
+
+    pub fn with_tropism(mut self, tropism: WorldVector, susceptibility: f64) -> Self {
+        self.tropism = tropism;
+        self.susceptibility = susceptibility;
+        self
+    }
+
+    /// Panics if `step` is null.
+    pub fn with_step(mut self, step: WorldVector) -> Self {
+        self.step = step.try_normalize().expect("step can't be null");
+        self
+    }
+
+    pub fn turtle(&self) -> Turtle {
+        Turtle {
+            position: self.position,
+            heading: self.heading,
+            left: self.left,
+            up: self.up,
+            width: self.width,
+            color: self.color,
+            angle_offset: 0.0,
+            step_scale: 1.0,
+            susceptibility: self.susceptibility,
+        }
+    }
+}
+
+/// Position, orientation and drawing state of the turtle. Heading, left and
+/// up form a right-handed orthonormal frame, `heading × left = up`.
+#[derive(Clone, Copy, Debug)]
+pub struct Turtle {
+    pub position: WorldPoint,
+    pub heading: WorldVector,
+    pub left: WorldVector,
+    pub up: WorldVector,
+    pub width: f64,
+    pub color: usize,
+    /// Added to the angles of `Params` by every rotation.
+    pub angle_offset: f64,
+    /// Multiplies the length of every step.
+    pub step_scale: f64,
+    pub susceptibility: f64,
+}
+
+impl Turtle {
+    /// Moves by `length` along `step`, expressed in the turtle's frame.
+    pub fn forward(&mut self, length: f64, step: WorldVector) {
+        self.position += (self.heading * step.x + self.left * step.y + self.up * step.z)
+            * length
+            * self.step_scale;
+    }
+
+    /// Applies the commands that only change the turtle's orientation or
+    /// drawing state, ignoring the others.
+    pub fn steer(&mut self, command: &TurtlePolygon, params: &Params) {
+        let offset = self.angle_offset;
+        match *command {
+            TurtlePolygon::Left => self.turn(params.angle + offset),
+            TurtlePolygon::Right => self.turn(-(params.angle + offset)),
+            TurtlePolygon::PitchDown => self.pitch(-(params.pitch_angle + offset)),
+            TurtlePolygon::PitchUp => self.pitch(params.pitch_angle + offset),
+            TurtlePolygon::RollLeft => self.roll(-(params.roll_angle + offset)),
+            TurtlePolygon::RollRight => self.roll(params.roll_angle + offset),
+            TurtlePolygon::TurnAround => self.turn(std::f64::consts::PI),
+            TurtlePolygon::RollHorizontal => self.roll_horizontal(params.vertical),
+            TurtlePolygon::SetWidth(width) => self.width = width,
+            TurtlePolygon::DecrementWidth => {
+                self.width = (self.width - params.width_decrement).max(0.0)
+            }
+            TurtlePolygon::SetColor(color) => self.color = color,
+            TurtlePolygon::NextColor => self.color += 1,
+            TurtlePolygon::IncrementAngle => self.angle_offset += params.angle_increment,
+            TurtlePolygon::DecrementAngle => self.angle_offset -= params.angle_increment,
+            TurtlePolygon::ScaleStep => self.step_scale *= params.step_factor,
+            TurtlePolygon::SetSusceptibility(susceptibility) => {
+                self.susceptibility = susceptibility
+            }
+            _ => {}
+        }
+    }
+
+    /// Turns around the up vector, the heading moving towards the left
+    /// vector for positive angles.
+    pub fn turn(&mut self, angle: f64) {
+        let (sin, cos) = angle.sin_cos();
+        (self.heading, self.left) = (
+            self.heading * cos + self.left * sin,
+            self.left * cos - self.heading * sin,
+        );
+    }
+
+    /// Pitches around the left vector, the heading moving towards the up
+    /// vector for positive angles.
+    pub fn pitch(&mut self, angle: f64) {
+        let (sin, cos) = angle.sin_cos();
+        (self.heading, self.up) = (
+            self.heading * cos + self.up * sin,
+            self.up * cos - self.heading * sin,
+        );
+    }
+
+    /// Rolls around the heading, the left vector moving towards the up
+    /// vector for positive angles.
+    pub fn roll(&mut self, angle: f64) {
+        let (sin, cos) = angle.sin_cos();
+        (self.left, self.up) = (
+            self.left * cos + self.up * sin,
+            self.up * cos - self.left * sin,
+        );
+    }
+
+    /// Rotates the whole frame around `heading × tropism`, bending the heading
+    /// towards `tropism` by `susceptibility * |heading × tropism|`.
+    pub fn bend(&mut self, tropism: WorldVector) {
+        let axis = self.heading.cross(tropism);
+        let length = axis.length();
+        if length <= f64::EPSILON {
+            return;
+        }
+        let axis = axis / length;
+        let (sin, cos) = (self.susceptibility * length).sin_cos();
+        let rotate = |vector: WorldVector| {
+            vector * cos + axis.cross(vector) * sin + axis * axis.dot(vector) * (1.0 - cos)
+        };
+        self.heading = rotate(self.heading);
+        self.left = rotate(self.left);
+        self.up = rotate(self.up);
+    }
+
+    /// Rolls around the heading until the left vector is horizontal. Does
+    /// nothing when heading straight up or down.
+    pub fn roll_horizontal(&mut self, vertical: WorldVector) {
+        let left = vertical.cross(self.heading);
+        if left.square_length() > f64::EPSILON {
+            self.left = left.normalize();
+            self.up = self.heading.cross(self.left);
+        }
+    }
+}
+
+/// Checks that every `Push` is matched by a `Pop` and every `NewPolygon` by a
+/// `ClosePolygon`, without collecting anything.
+pub fn validate<T: TurtlePolygonInterpretation>(commands: &[T]) -> Result<(), TurtleError> {
+    walk(commands, &Params::new(0.0), &mut ())
+}
+
+/// What the turtle knew when it emitted a vertex.
+#[derive(Clone, Debug)]
+pub struct VertexAttributes<P> {
+    /// Index of the command that emitted the vertex.
+    pub index: usize,
+    /// Number of `Push` still open.
+    pub depth: usize,
+    /// Number of `NewPolygon` still open, the vertex's own polygon included.
+    pub polygon_depth: usize,
+    pub heading: WorldVector,
+    pub width: f64,
+    pub color: usize,
+    pub payload: P,
+}
+
+/// Polygon whose `attributes` run alongside its `points`.
+#[derive(Clone, Debug)]
+pub struct AttributedPolygon<P> {
+    pub points: Vec<WorldPoint>,
+    pub attributes: Vec<VertexAttributes<P>>,
+}
+
+impl<P> AttributedPolygon<P> {
+    fn new() -> Self {
+        Self {
+            points: vec![],
+            attributes: vec![],
+        }
+    }
+}
+
+/// Backend collecting the polygons drawn between `NewPolygon` and
+/// `ClosePolygon`. The payload is turtle state like its position: it starts
+/// as `P::default()`, is replaced whenever `payload` returns something for a
+/// symbol, and is saved and restored by `Push` and `Pop`.
+pub struct PolygonCollector<P, F> {
+    payload: F,
+    current: P,
+    saved_payloads: Vec<P>,
+    polygon: AttributedPolygon<P>,
+    saved_polygons: Vec<AttributedPolygon<P>>,
+    polygons: Vec<AttributedPolygon<P>>,
+}
+
+impl<P: Default, F> PolygonCollector<P, F> {
+    pub fn new(payload: F) -> Self {
+        Self {
+            payload,
+            current: P::default(),
+            saved_payloads: vec![],
+            polygon: AttributedPolygon::new(),
+            saved_polygons: vec![],
+            polygons: vec![],
+        }
+    }
+
+    pub fn into_polygons(self) -> Vec<AttributedPolygon<P>> {
+        self.polygons
+    }
+}
+
+impl<T, P, F> TurtleBackend<T> for PolygonCollector<P, F>
+where
+    P: Clone + Default,
+    F: FnMut(&T) -> Option<P>,
+{
+    fn symbol(&mut self, symbol: &T, _index: usize) {
+        if let Some(value) = (self.payload)(symbol) {
+            self.current = value;
+        }
+    }
+
+    fn vertex(&mut self, turtle: &Turtle, index: usize) {
+        self.polygon.points.push(turtle.position);
+        self.polygon.attributes.push(VertexAttributes {
+            index,
+            depth: self.saved_payloads.len(),
+            polygon_depth: self.saved_polygons.len(),
+            heading: turtle.heading,
+            width: turtle.width,
+            color: turtle.color,
+            payload: self.current.clone(),
+        });
+    }
+
+    fn begin_polygon(&mut self, _turtle: &Turtle, _index: usize) {
+        let polygon = std::mem::replace(&mut self.polygon, AttributedPolygon::new());
+        self.saved_polygons.push(polygon);
+    }
+
+    fn end_polygon(&mut self, _turtle: &Turtle, _index: usize) {
+        if let Some(saved) = self.saved_polygons.pop() {
+            let polygon = std::mem::replace(&mut self.polygon, saved);
+            self.polygons.push(polygon);
+        }
+    }
+
+    fn push(&mut self, _turtle: &Turtle, _index: usize) {
+        self.saved_payloads.push(self.current.clone());
+    }
+
+    fn pop(&mut self, _turtle: &Turtle, _index: usize) {
+        if let Some(payload) = self.saved_payloads.pop() {
+            self.current = payload;
+        }
     }
 }
 
 pub fn to_geom<T: TurtlePolygonInterpretation + Debug>(
     commands: Vec<T>,
     params: &Params,
-) -> Vec<Vec<WorldPoint>> {
-    let mut polygons = vec![];
-
-    let mut position = WorldVector::zero();
-    let mut rotation = WorldRotation::around_z(Angle::radians(FRAC_PI_4));
-    let mut states = VecDeque::new();
-    let mut points = vec![];
-    let mut saved_points = VecDeque::new();
-    for command in commands.iter() {
-        match command.to_turtle() {
-            TurtlePolygon::Vertex => points.push(position.to_point()),
-            TurtlePolygon::Forward(length) => {
-                position += rotation.transform_vector3d(WorldVector::one()) * length
-            }
-            TurtlePolygon::Left => {
-                rotation = rotation.then(&WorldRotation::around_z(Angle::radians(params.angle)));
-            }
-            TurtlePolygon::Right => {
-                rotation = rotation.then(&WorldRotation::around_z(Angle::radians(-params.angle)));
-            }
-            TurtlePolygon::Push => states.push_back((position, rotation)),
-            TurtlePolygon::Pop => (position, rotation) = states.pop_back().unwrap(),
-            TurtlePolygon::NewPolygon => {
-                saved_points.push_back(points);
-                points = vec![];
-            }
-            TurtlePolygon::ClosePolygon => {
-                polygons.push(points);
-                points = saved_points.pop_back().unwrap();
+) -> Result<Vec<Vec<WorldPoint>>, TurtleError> {
+    Ok(to_attributed_geom(&commands, params, |_| None::<()>)?
+        .into_iter()
+        .map(|polygon| polygon.points)
+        .collect())
+}
+
+/// Same as `to_geom`, with attributes for every vertex. See
+/// `PolygonCollector` for how `payload` is tracked.
+pub fn to_attributed_geom<T, P, F>(
+    commands: &[T],
+    params: &Params,
+    payload: F,
+) -> Result<Vec<AttributedPolygon<P>>, TurtleError>
+where
+    T: TurtlePolygonInterpretation,
+    P: Clone + Default,
+    F: FnMut(&T) -> Option<P>,
+{
+    let mut collector = PolygonCollector::new(payload);
+    walk(commands, params, &mut collector)?;
+    Ok(collector.into_polygons())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::grammar::module::Module;
+    use crate::turtle::{error::TurtleErrorKind, skeleton::to_skeleton};
+
+    fn modules(source: &str) -> Vec<Module> {
+        source
+            .split_whitespace()
+            .map(|name| Module::new(name, vec![]))
+            .collect()
+    }
+
+    fn assert_orthonormal(turtle: &Turtle) {
+        for vector in [turtle.heading, turtle.left, turtle.up] {
+            assert!((vector.length() - 1.0).abs() < 1e-9, "{:?}", turtle);
+        }
+        assert!(turtle.heading.dot(turtle.left).abs() < 1e-9, "{:?}", turtle);
+        assert!(
+            (turtle.heading.cross(turtle.left) - turtle.up).length() < 1e-9,
+            "{:?}",
+            turtle
+        );
+    }
+
+    #[test]
+    fn forward_follows_the_heading() {
+        let params = Params::new(0.3);
+        let mut turtle = params.turtle();
+        turtle.forward(1.0, params.step);
+        assert_eq!(turtle.position, WorldPoint::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rotations_keep_the_frame_orthonormal() {
+        let params = Params::new(0.3);
+        let mut turtle = params.turtle();
+        let commands = [
+            TurtlePolygon::Left,
+            TurtlePolygon::PitchDown,
+            TurtlePolygon::RollRight,
+            TurtlePolygon::TurnAround,
+            TurtlePolygon::PitchUp,
+            TurtlePolygon::RollLeft,
+            TurtlePolygon::Right,
+            TurtlePolygon::RollHorizontal,
+        ];
+        for _ in 0..100 {
+            for command in &commands {
+                turtle.steer(command, &params);
+                assert_orthonormal(&turtle);
             }
-            TurtlePolygon::None => {}
         }
+        let heading = turtle.heading;
+        turtle.steer(&TurtlePolygon::TurnAround, &params);
+        assert!((turtle.heading + heading).length() < 1e-9);
+    }
+
+    #[test]
+    fn validate_reports_where_strings_are_malformed() {
+        let error = |source| validate(&modules(source)).unwrap_err();
+        assert_eq!(
+            error("[ F ] F ] ["),
+            TurtleError::new(4, TurtleErrorKind::UnmatchedPop)
+        );
+        assert_eq!(
+            error("{ . } . }"),
+            TurtleError::new(4, TurtleErrorKind::UnmatchedClosePolygon)
+        );
+        assert_eq!(
+            error("[ F [ F ] F"),
+            TurtleError::new(0, TurtleErrorKind::UnclosedPush)
+        );
+        assert_eq!(
+            error("{ . } { . { . }"),
+            TurtleError::new(3, TurtleErrorKind::UnclosedPolygon)
+        );
+        assert!(validate(&modules("[ F { . F . } ] F")).is_ok());
+    }
+
+    #[test]
+    fn heading_bends_towards_tropism() {
+        let params = Params::new(0.0).with_tropism(WorldVector::new(1.0, 0.0, 0.0), 0.2);
+        let mut turtle = params.turtle();
+        turtle.bend(params.tropism);
+        let expected = WorldVector::new(0.2f64.sin(), 0.2f64.cos(), 0.0);
+        assert!((turtle.heading - expected).length() < 1e-9);
+        assert_orthonormal(&turtle);
+
+        let segments = to_skeleton(&modules("F F F F F F"), &params).unwrap();
+        let directions: Vec<_> = segments
+            .iter()
+            .map(|segment| (segment.end - segment.start).normalize())
+            .collect();
+        assert_eq!(directions[0], params.heading);
+        for pair in directions.windows(2) {
+            assert!(pair[1].x > pair[0].x && pair[1].x < 1.0, "{:?}", directions);
+        }
+    }
+
+    #[test]
+    fn frames_are_made_orthonormal() {
+        let params = Params::new(0.3)
+            .with_frame(
+                WorldVector::new(2.0, 0.0, 0.0),
+                WorldVector::new(1.0, 3.0, 0.0),
+            )
+            .with_step(WorldVector::new(0.0, 0.0, 2.0));
+        let mut turtle = params.turtle();
+        assert_orthonormal(&turtle);
+        assert_eq!(turtle.left, WorldVector::new(0.0, 1.0, 0.0));
+        turtle.forward(1.0, params.step);
+        assert_eq!(turtle.position, WorldPoint::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "left can't be parallel to the heading")]
+    fn frames_need_distinct_directions() {
+        Params::new(0.3).with_frame(
+            WorldVector::new(0.0, 1.0, 0.0),
+            WorldVector::new(0.0, -2.0, 0.0),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "step can't be null")]
+    fn steps_need_a_direction() {
+        Params::new(0.3).with_step(WorldVector::zero());
     }
-    polygons
 }
@@ -0,0 +1,107 @@
+use super::{
+  backend::{walk, TurtleBackend},
+  error::TurtleError,
+  polygon::{Params, Turtle, TurtlePolygonInterpretation},
+};
+use crate::utils::geometry::WorldPoint;
+
+/// Line drawn by a single `Forward`, polygons included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+  pub start: WorldPoint,
+  pub end: WorldPoint,
+  /// Number of `Push` still open when the segment was drawn.
+  pub depth: usize,
+  pub width: f64,
+  pub color: usize,
+  /// Index of the command that drew the segment.
+  pub index: usize,
+  /// Segment the turtle drew last before this one on the same branch, or on
+  /// the branch it sprouted from.
+  pub parent: Option<usize>,
+}
+
+/// Backend collecting every line drawn, as a branching skeleton.
+#[derive(Default)]
+pub struct SegmentCollector {
+  segments: Vec<Segment>,
+  current: Option<usize>,
+  saved: Vec<Option<usize>>,
+}
+
+impl SegmentCollector {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn into_segments(self) -> Vec<Segment> {
+    self.segments
+  }
+}
+
+impl<T> TurtleBackend<T> for SegmentCollector {
+  fn line_to(&mut self, from: &Turtle, turtle: &Turtle, index: usize) {
+    self.segments.push(Segment {
+      start: from.position,
+      end: turtle.position,
+      depth: self.saved.len(),
+      width: turtle.width,
+      color: turtle.color,
+      index,
+      parent: self.current,
+    });
+    self.current = Some(self.segments.len() - 1);
+  }
+
+  fn push(&mut self, _turtle: &Turtle, _index: usize) {
+    self.saved.push(self.current);
+  }
+
+  fn pop(&mut self, _turtle: &Turtle, _index: usize) {
+    if let Some(current) = self.saved.pop() {
+      self.current = current;
+    }
+  }
+}
+
+/// Interprets `commands` as a branching skeleton, where `to_geom` only keeps
+/// polygons.
+pub fn to_skeleton<T: TurtlePolygonInterpretation>(
+  commands: &[T],
+  params: &Params,
+) -> Result<Vec<Segment>, TurtleError> {
+  let mut collector = SegmentCollector::new();
+  walk(commands, params, &mut collector)?;
+  Ok(collector.into_segments())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::systems::grammar::module::Module;
+
+  #[test]
+  fn branches_hang_from_the_segment_they_sprout_from() {
+    let modules: Vec<_> = "F [ + F F ] F [ F ]"
+      .split_whitespace()
+      .map(|name| Module::new(name, vec![]))
+      .collect();
+    let segments = to_skeleton(&modules, &Params::new(0.5)).unwrap();
+    let summary: Vec<_> = segments
+      .iter()
+      .map(|segment| (segment.index, segment.parent, segment.depth))
+      .collect();
+    assert_eq!(
+      summary,
+      [
+        (0, None, 0),
+        (3, Some(0), 1),
+        (4, Some(1), 1),
+        (6, Some(0), 0),
+        (8, Some(3), 1),
+      ]
+    );
+    assert_eq!(segments[3].start, WorldPoint::new(0.0, 1.0, 0.0));
+    assert_eq!(segments[4].start, segments[3].end);
+  }
+}
@@ -0,0 +1,238 @@
+use super::{
+  backend::{walk, TurtleBackend},
+  error::TurtleError,
+  polygon::{Params, Turtle, TurtlePolygonInterpretation},
+};
+use crate::utils::geometry::{Mesh, WorldPoint, WorldRotation, WorldVector};
+use euclid::Angle;
+use std::f64::consts::TAU;
+
+/// Cross-section laid down at the end of a segment.
+#[derive(Clone, Copy)]
+struct Ring {
+  first: u32,
+  center: WorldPoint,
+  tangent: WorldVector,
+  color: usize,
+}
+
+/// End of the tube being swept on the current branch, and the frame it was
+/// swept with so the next section can carry it on.
+#[derive(Clone, Copy, Default)]
+struct Tip {
+  ring: Option<Ring>,
+  frame: Option<(WorldVector, WorldVector)>,
+}
+
+/// Backend sweeping a cross-section along every line drawn, its radius
+/// being half the turtle's width. Frames are parallel-transported from one
+/// segment to the next so tubes don't twist; every branch starts and ends
+/// with a cap.
+pub struct TubeBuilder {
+  profile: Vec<(f64, f64)>,
+  mesh: Mesh,
+  tip: Tip,
+  saved: Vec<Tip>,
+}
+
+impl TubeBuilder {
+  /// Circular cross-sections with `sides` sides.
+  pub fn new(sides: usize) -> Self {
+    Self::with_profile(
+      (0..sides)
+        .map(|side| {
+          let (sin, cos) = (TAU * side as f64 / sides as f64).sin_cos();
+          (cos, sin)
+        })
+        .collect(),
+    )
+  }
+
+  /// Cross-sections following `profile`, a counter-clockwise outline of
+  /// unit radius, `(x, y)` being along the section's normal and binormal.
+  /// Vertex normals point away from the section's center.
+  pub fn with_profile(profile: Vec<(f64, f64)>) -> Self {
+    Self {
+      profile,
+      mesh: Mesh::new(),
+      tip: Tip::default(),
+      saved: vec![],
+    }
+  }
+
+  pub fn into_mesh(mut self) -> Mesh {
+    self.end_tube();
+    self.mesh
+  }
+
+  fn ring(
+    &mut self,
+    center: WorldPoint,
+    tangent: WorldVector,
+    normal: WorldVector,
+    radius: f64,
+    color: usize,
+  ) -> Ring {
+    let binormal = tangent.cross(normal);
+    let first = self.mesh.positions.len() as u32;
+    for (x, y) in self.profile.iter() {
+      let direction = normal * *x + binormal * *y;
+      self.mesh.push_vertex(
+        center + direction * radius,
+        direction.try_normalize().unwrap_or(direction),
+        color,
+      );
+    }
+    Ring {
+      first,
+      center,
+      tangent,
+      color,
+    }
+  }
+
+  fn connect(&mut self, from: &Ring, to: &Ring) {
+    let sides = self.profile.len() as u32;
+    for side in 0..sides {
+      let next = (side + 1) % sides;
+      let (a, b) = (from.first + side, from.first + next);
+      let (c, d) = (to.first + next, to.first + side);
+      self.mesh.push_triangle(a, b, c);
+      self.mesh.push_triangle(a, c, d);
+    }
+  }
+
+  /// Closes a ring with a flat fan, facing along the tangent at the end of a
+  /// tube and against it at its start.
+  fn cap(&mut self, ring: &Ring, end: bool) {
+    let normal = if end { ring.tangent } else { -ring.tangent };
+    let center = self.mesh.push_vertex(ring.center, normal, ring.color);
+    let sides = self.profile.len() as u32;
+    for side in 0..sides {
+      let position = self.mesh.positions[(ring.first + side) as usize];
+      self.mesh.push_vertex(position, normal, ring.color);
+    }
+    for side in 0..sides {
+      let (a, b) = (center + 1 + side, center + 1 + (side + 1) % sides);
+      if end {
+        self.mesh.push_triangle(center, a, b);
+      } else {
+        self.mesh.push_triangle(center, b, a);
+      }
+    }
+  }
+
+  fn end_tube(&mut self) {
+    if let Some(ring) = self.tip.ring.take() {
+      self.cap(&ring, true);
+    }
+  }
+}
+
+fn perpendicular(vector: WorldVector, tangent: WorldVector) -> Option<WorldVector> {
+  (vector - tangent * tangent.dot(vector)).try_normalize()
+}
+
+/// Rotates `normal` by the smallest rotation taking `from` onto `to`.
+fn transport(normal: WorldVector, from: WorldVector, to: WorldVector) -> WorldVector {
+  let axis = from.cross(to);
+  let sin = axis.length();
+  if sin <= f64::EPSILON {
+    return perpendicular(normal, to).unwrap_or(normal);
+  }
+  let angle = Angle::radians(sin.atan2(from.dot(to)));
+  WorldRotation::around_axis(axis, angle).transform_vector3d(normal)
+}
+
+impl<T> TurtleBackend<T> for TubeBuilder {
+  fn line_to(&mut self, from: &Turtle, turtle: &Turtle, _index: usize) {
+    let Some(tangent) = (turtle.position - from.position).try_normalize() else {
+      return;
+    };
+    let normal = match self.tip.frame {
+      Some((previous, normal)) => transport(normal, previous, tangent),
+      None => perpendicular(from.up, tangent)
+        .or_else(|| perpendicular(from.left, tangent))
+        .unwrap_or(from.up),
+    };
+    let start = match self.tip.ring {
+      Some(ring) => ring,
+      None => {
+        let ring = self.ring(from.position, tangent, normal, from.width / 2.0, from.color);
+        self.cap(&ring, false);
+        ring
+      }
+    };
+    let end = self.ring(
+      turtle.position,
+      tangent,
+      normal,
+      turtle.width / 2.0,
+      turtle.color,
+    );
+    self.connect(&start, &end);
+    self.tip = Tip {
+      ring: Some(end),
+      frame: Some((tangent, normal)),
+    };
+  }
+
+  fn move_to(&mut self, _turtle: &Turtle, _index: usize) {
+    self.end_tube();
+  }
+
+  fn push(&mut self, _turtle: &Turtle, _index: usize) {
+    self.saved.push(self.tip);
+    self.tip.ring = None;
+  }
+
+  fn pop(&mut self, _turtle: &Turtle, _index: usize) {
+    self.end_tube();
+    self.tip = self.saved.pop().unwrap_or_default();
+  }
+}
+
+/// Tube mesh around the skeleton drawn by `commands`, with circular
+/// sections of `sides` sides.
+pub fn to_tube_mesh<T: TurtlePolygonInterpretation>(
+  commands: &[T],
+  params: &Params,
+  sides: usize,
+) -> Result<Mesh, TurtleError> {
+  let mut builder = TubeBuilder::new(sides);
+  walk(commands, params, &mut builder)?;
+  Ok(builder.into_mesh())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::systems::grammar::module::Module;
+
+  const SIDES: usize = 6;
+
+  fn mesh(source: &str) -> Mesh {
+    let modules: Vec<_> = source
+      .split_whitespace()
+      .map(|name| Module::new(name, vec![]))
+      .collect();
+    to_tube_mesh(&modules, &Params::new(0.5), SIDES).unwrap()
+  }
+
+  #[test]
+  fn segments_share_rings_and_branches_are_capped() {
+    let tube = mesh("F F");
+    assert_eq!(tube.positions.len(), 3 * SIDES + 2 * (SIDES + 1));
+    assert_eq!(tube.triangles.len(), 2 * 2 * SIDES + 2 * SIDES);
+    for side in 0..SIDES {
+      let (start, middle) = (tube.positions[side], tube.positions[side + 2 * SIDES + 1]);
+      assert!((middle - start - WorldVector::new(0.0, 1.0, 0.0)).length() < 1e-9);
+      assert!(((start - WorldPoint::origin()).length() - 0.5).abs() < 1e-9);
+    }
+    assert_eq!(tube.normals[SIDES], WorldVector::new(0.0, -1.0, 0.0));
+
+    let branched = mesh("F [ + F ] F");
+    assert_eq!(branched.positions.len(), 5 * SIDES + 4 * (SIDES + 1));
+    assert_eq!(branched.triangles.len(), 3 * 2 * SIDES + 4 * SIDES);
+  }
+}
@@ -1,8 +1,8 @@
+use crate::utils::TEXTURE_SIZE;
 use nannou::{prelude::Key, wgpu, window, App, Draw, Frame};
 use rand::random;
 use std::path::PathBuf;
 
-const TEXTURE_SIZE: [u32; 2] = [2160, 2160];
 const RENDER_SIZE: [u32; 2] = [540, 540];
 
 pub trait Artwork {
@@ -94,7 +94,7 @@ fn make_base_model<T: 'static + Artwork>(app: &App, options: ArtworkOptions) ->
   });
   let seed = rand::random();
   // Make sure the directory where we will save images to exists.
-  std::fs::create_dir_all(&capture_directory(app)).unwrap();
+  std::fs::create_dir_all(capture_directory(app)).unwrap();
   BaseModel {
     window_id,
     draw,
@@ -1,13 +1,15 @@
-use super::geometry::{ScreenPoint, ScreenVector};
+use super::geometry::{
+  brush_points, fill_triangles, split_self_intersections, triangulate, ScreenPoint,
+};
 use nannou::{
+  color::{LinSrgba, Mix},
   draw::{
-    primitive::{Path, PathStroke},
+    primitive::{mesh::Vertexless, Mesh, Path, PathStroke},
     Drawing,
   },
   geom,
 };
-use rand::{rngs::StdRng, Rng};
-use std::{f64::consts::PI, ops::Add};
+use rand::rngs::StdRng;
 
 pub trait StrokeDrawer<'a> {
   fn stroke_from_points(self, points: &[ScreenPoint]) -> Drawing<'a, Path>;
@@ -40,16 +42,84 @@ impl<'a> BrushDrawer<'a> for Drawing<'a, PathStroke> {
     radius: f64,
     rng: &mut StdRng,
   ) -> Drawing<'a, Path> {
-    self.points((0..radius as usize).flat_map(|_| {
-      points
-        .iter()
-        .map(|p| {
-          let r = radius * rng.gen::<f64>().sqrt();
-          let theta = 2.0 * PI * rng.gen::<f64>().sqrt();
-          p.add(ScreenVector::new(theta.cos(), theta.sin()) * r)
-        })
-        .map(|p| geom::Point2::new(p.x as f32, p.y as f32))
-        .collect::<Vec<geom::Point2>>()
-    }))
+    self.points(
+      brush_points(points, radius, rng)
+        .into_iter()
+        .map(|p| geom::Point2::new(p.x as f32, p.y as f32)),
+    )
+  }
+}
+
+fn to_point3(point: ScreenPoint) -> geom::Point3 {
+  geom::Point3::new(point.x as f32, point.y as f32, 0.0)
+}
+
+fn colored_tris(points: &[(ScreenPoint, LinSrgba)]) -> Vec<geom::Tri<(geom::Point3, LinSrgba)>> {
+  split_self_intersections(
+    points,
+    |(point, _)| *point,
+    |(a, a_color), (b, b_color), t| (a.lerp(*b, t), a_color.mix(b_color, t as f32)),
+  )
+  .iter()
+  .flat_map(|polygon| {
+    let positions = polygon.iter().map(|(point, _)| *point).collect::<Vec<_>>();
+    triangulate(&positions).into_iter().map(|triangle| {
+      geom::Tri(triangle.map(|vertex| {
+        let (point, color) = polygon[vertex];
+        (to_point3(point), color)
+      }))
+    })
+  })
+  .collect()
+}
+
+/// Filled outlines, self-intersecting ones included.
+pub trait FillDrawer<'a> {
+  /// Single color, set with `color` on the result.
+  fn fill_from_points(self, points: &[ScreenPoint]) -> Drawing<'a, Mesh>;
+  /// Linear gradient from `start` to `end`, flat beyond them.
+  fn gradient_from_points(
+    self,
+    points: &[ScreenPoint],
+    start: (ScreenPoint, LinSrgba),
+    end: (ScreenPoint, LinSrgba),
+  ) -> Drawing<'a, Mesh>;
+  /// Colors interpolated between those of the vertices.
+  fn colored_from_points(self, points: &[(ScreenPoint, LinSrgba)]) -> Drawing<'a, Mesh>;
+}
+
+impl<'a> FillDrawer<'a> for Drawing<'a, Vertexless> {
+  fn fill_from_points(self, points: &[ScreenPoint]) -> Drawing<'a, Mesh> {
+    self.tris(
+      fill_triangles(points)
+        .into_iter()
+        .map(|triangle| geom::Tri(triangle.map(to_point3))),
+    )
+  }
+
+  fn gradient_from_points(
+    self,
+    points: &[ScreenPoint],
+    (start, start_color): (ScreenPoint, LinSrgba),
+    (end, end_color): (ScreenPoint, LinSrgba),
+  ) -> Drawing<'a, Mesh> {
+    let axis = end - start;
+    let length = axis.square_length();
+    let points = points
+      .iter()
+      .map(|point| {
+        let t = if length > 0.0 {
+          ((*point - start).dot(axis) / length).clamp(0.0, 1.0)
+        } else {
+          0.0
+        };
+        (*point, start_color.mix(&end_color, t as f32))
+      })
+      .collect::<Vec<_>>();
+    self.colored_from_points(&points)
+  }
+
+  fn colored_from_points(self, points: &[(ScreenPoint, LinSrgba)]) -> Drawing<'a, Mesh> {
+    self.tris_colored(colored_tris(points))
   }
 }
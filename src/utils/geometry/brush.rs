@@ -0,0 +1,20 @@
+use super::{ScreenPoint, ScreenVector};
+use rand::{rngs::StdRng, Rng};
+use std::f64::consts::PI;
+
+/// Points scattered around `points` to imitate a dry brush, `radius` times
+/// over, each within `radius` of the point it comes from.
+pub fn brush_points(points: &[ScreenPoint], radius: f64, rng: &mut StdRng) -> Vec<ScreenPoint> {
+  (0..radius as usize)
+    .flat_map(|_| {
+      points
+        .iter()
+        .map(|p| {
+          let r = radius * rng.gen::<f64>().sqrt();
+          let theta = 2.0 * PI * rng.gen::<f64>().sqrt();
+          *p + ScreenVector::new(theta.cos(), theta.sin()) * r
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
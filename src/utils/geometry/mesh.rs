@@ -0,0 +1,92 @@
+use super::{split_self_intersections, triangulate, ScreenPoint, WorldPoint, WorldVector};
+
+/// Indexed triangle mesh, counter-clockwise triangles facing outwards. Every
+/// vertex has a normal and a color index.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+  pub positions: Vec<WorldPoint>,
+  pub normals: Vec<WorldVector>,
+  pub colors: Vec<usize>,
+  pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.triangles.is_empty()
+  }
+
+  /// Adds a vertex and returns its index.
+  pub fn push_vertex(&mut self, position: WorldPoint, normal: WorldVector, color: usize) -> u32 {
+    self.positions.push(position);
+    self.normals.push(normal);
+    self.colors.push(color);
+    (self.positions.len() - 1) as u32
+  }
+
+  pub fn push_triangle(&mut self, a: u32, b: u32, c: u32) {
+    self.triangles.push([a, b, c]);
+  }
+
+  pub fn append(&mut self, other: &Mesh) {
+    let offset = self.positions.len() as u32;
+    self.positions.extend(&other.positions);
+    self.normals.extend(&other.normals);
+    self.colors.extend(&other.colors);
+    self.triangles.extend(
+      other
+        .triangles
+        .iter()
+        .map(|[a, b, c]| [a + offset, b + offset, c + offset]),
+    );
+  }
+
+  /// Adds a flat polygon facing the way it winds, triangulated in its mean
+  /// plane and split where it crosses itself. Each vertex comes with its
+  /// color; a vertex where the outline crosses itself takes the color of the
+  /// edge it splits.
+  pub fn push_polygon(&mut self, vertices: &[(WorldPoint, usize)]) {
+    // Newell's normal, which is also twice the polygon's area.
+    let normal = vertices
+      .iter()
+      .zip(vertices.iter().cycle().skip(1))
+      .fold(WorldVector::zero(), |normal, ((a, _), (b, _))| {
+        normal + a.to_vector().cross(b.to_vector())
+      });
+    let Some(normal) = normal.try_normalize() else {
+      return;
+    };
+    let axis = if normal.x.abs() < 0.9 {
+      WorldVector::new(1.0, 0.0, 0.0)
+    } else {
+      WorldVector::new(0.0, 1.0, 0.0)
+    };
+    let u = (axis - normal * normal.dot(axis)).normalize();
+    let v = normal.cross(u);
+    let project = |(point, _): &(WorldPoint, usize)| {
+      ScreenPoint::new(point.to_vector().dot(u), point.to_vector().dot(v))
+    };
+
+    for polygon in split_self_intersections(vertices, project, |(a, color), (b, _), t| {
+      (a.lerp(*b, t), *color)
+    }) {
+      let points = polygon.iter().map(project).collect::<Vec<_>>();
+      let first = self.positions.len() as u32;
+      for (position, color) in polygon.iter() {
+        self.push_vertex(*position, normal, *color);
+      }
+      for [a, b, c] in triangulate(&points) {
+        // Loops split off a self-intersecting outline may wind backwards.
+        let (b, c) = if (points[b] - points[a]).cross(points[c] - points[a]) < 0.0 {
+          (c, b)
+        } else {
+          (b, c)
+        };
+        self.push_triangle(first + a as u32, first + b as u32, first + c as u32);
+      }
+    }
+  }
+}
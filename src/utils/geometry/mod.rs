@@ -1,5 +1,13 @@
+mod brush;
+mod mesh;
+mod triangulate;
+
 use euclid::{Box3D, Point2D, Point3D, Rotation3D, Size2D, Transform3D, Vector2D, Vector3D};
 
+pub use brush::brush_points;
+pub use mesh::Mesh;
+pub use triangulate::{fill_triangles, split_self_intersections, triangulate};
+
 pub struct ScreenSpace;
 pub type ScreenVector = Vector2D<f64, ScreenSpace>;
 pub type ScreenPoint = Point2D<f64, ScreenSpace>;
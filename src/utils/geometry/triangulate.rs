@@ -0,0 +1,235 @@
+use super::{ScreenPoint, ScreenVector};
+use std::iter::once;
+
+fn cross(a: ScreenVector, b: ScreenVector) -> f64 {
+  a.x * b.y - a.y * b.x
+}
+
+/// Twice the signed area of a polygon, positive when counter-clockwise.
+fn signed_area(points: &[ScreenPoint], indices: &[usize]) -> f64 {
+  indices
+    .iter()
+    .zip(indices.iter().cycle().skip(1))
+    .map(|(a, b)| cross(points[*a].to_vector(), points[*b].to_vector()))
+    .sum()
+}
+
+fn contains(triangle: [ScreenPoint; 3], point: ScreenPoint) -> bool {
+  let [a, b, c] = triangle;
+  let sides = [
+    cross(b - a, point - a),
+    cross(c - b, point - b),
+    cross(a - c, point - c),
+  ];
+  sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+}
+
+/// Triangulates a simple polygon by ear clipping, in either orientation.
+/// Returns triangles as indices into `points`, wound like the polygon.
+///
+/// Repeated and collinear vertices are dropped on the way. Should the
+/// polygon not be simple after all, vertices that aren't ears get clipped
+/// anyway once none is left, so this always terminates with a best effort.
+pub fn triangulate(points: &[ScreenPoint]) -> Vec<[usize; 3]> {
+  let mut remaining = (0..points.len()).collect::<Vec<_>>();
+  remaining.dedup_by(|a, b| points[*a] == points[*b]);
+  while remaining.len() > 1 && points[remaining[0]] == points[*remaining.last().unwrap()] {
+    remaining.pop();
+  }
+  let orientation = signed_area(points, &remaining).signum();
+  if remaining.len() < 3 || orientation == 0.0 {
+    return vec![];
+  }
+
+  let mut triangles = vec![];
+  let mut index = 0;
+  let mut misses = 0;
+  while remaining.len() > 3 {
+    let count = remaining.len();
+    index %= count;
+    let ear = [
+      remaining[(index + count - 1) % count],
+      remaining[index],
+      remaining[(index + 1) % count],
+    ];
+    let [a, b, c] = ear.map(|vertex| points[vertex]);
+    let turn = cross(b - a, c - b) * orientation;
+    let is_ear = turn > 0.0
+      && remaining
+        .iter()
+        .filter(|vertex| !ear.contains(vertex))
+        .all(|vertex| !contains([a, b, c], points[*vertex]));
+    if turn == 0.0 || is_ear || misses >= count {
+      if turn > 0.0 {
+        triangles.push(ear);
+      }
+      remaining.remove(index);
+      misses = 0;
+    } else {
+      index += 1;
+      misses += 1;
+    }
+  }
+  let [a, b, c] = [remaining[0], remaining[1], remaining[2]];
+  if cross(points[b] - points[a], points[c] - points[b]) * orientation > 0.0 {
+    triangles.push([a, b, c]);
+  }
+  triangles
+}
+
+/// First pair of edges crossing each other away from their ends, as the
+/// indices of their first vertices along with where the crossing lies on the
+/// first one.
+fn first_crossing(points: &[ScreenPoint]) -> Option<(usize, usize, f64)> {
+  let count = points.len();
+  for i in 0..count {
+    let (a, b) = (points[i], points[(i + 1) % count]);
+    for j in i + 2..count {
+      if i == 0 && j == count - 1 {
+        continue;
+      }
+      let (c, d) = (points[j], points[(j + 1) % count]);
+      let (r, s) = (b - a, d - c);
+      let denominator = cross(r, s);
+      if denominator == 0.0 {
+        continue;
+      }
+      let t = cross(c - a, s) / denominator;
+      let u = cross(c - a, r) / denominator;
+      if t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0 {
+        return Some((i, j, t));
+      }
+    }
+  }
+  None
+}
+
+/// Splits a self-intersecting outline at its crossings into simple loops.
+/// Vertices carry whatever the caller needs: `position` locates them, and
+/// `lerp` makes the vertex at a crossing from the two ends of the first
+/// edge involved.
+///
+/// Filling every loop matches the nonzero rule, as long as loops don't wind
+/// against each other.
+pub fn split_self_intersections<V: Clone>(
+  vertices: &[V],
+  position: impl Fn(&V) -> ScreenPoint,
+  lerp: impl Fn(&V, &V, f64) -> V,
+) -> Vec<Vec<V>> {
+  let mut pending = vec![vertices.to_vec()];
+  let mut loops = vec![];
+  while let Some(polygon) = pending.pop() {
+    let points = polygon.iter().map(&position).collect::<Vec<_>>();
+    match first_crossing(&points) {
+      None if polygon.len() >= 3 => loops.push(polygon),
+      None => {}
+      Some((i, j, t)) => {
+        let crossing = lerp(&polygon[i], &polygon[i + 1], t);
+        pending.push(
+          polygon[..=i]
+            .iter()
+            .cloned()
+            .chain(once(crossing.clone()))
+            .chain(polygon[j + 1..].iter().cloned())
+            .collect(),
+        );
+        pending.push(
+          once(crossing)
+            .chain(polygon[i + 1..=j].iter().cloned())
+            .collect(),
+        );
+      }
+    }
+  }
+  loops
+}
+
+/// Triangles filling an outline, whether it crosses itself or not.
+pub fn fill_triangles(points: &[ScreenPoint]) -> Vec<[ScreenPoint; 3]> {
+  split_self_intersections(points, |point| *point, |a, b, t| a.lerp(*b, t))
+    .iter()
+    .flat_map(|polygon| {
+      triangulate(polygon)
+        .into_iter()
+        .map(|triangle| triangle.map(|vertex| polygon[vertex]))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn outline(coordinates: &[(f64, f64)]) -> Vec<ScreenPoint> {
+    coordinates
+      .iter()
+      .map(|&(x, y)| ScreenPoint::new(x, y))
+      .collect()
+  }
+
+  /// Twice the area of each triangle, signed like its winding.
+  fn areas(triangles: &[[ScreenPoint; 3]]) -> Vec<f64> {
+    triangles
+      .iter()
+      .map(|[a, b, c]| cross(*b - *a, *c - *b))
+      .collect()
+  }
+
+  fn indexed(points: &[ScreenPoint]) -> Vec<[ScreenPoint; 3]> {
+    triangulate(points)
+      .into_iter()
+      .map(|triangle| triangle.map(|vertex| points[vertex]))
+      .collect()
+  }
+
+  #[test]
+  fn concave_outlines_are_covered_once() {
+    let mut points = outline(&[
+      (0.0, 0.0),
+      (2.0, 0.0),
+      (2.0, 1.0),
+      (1.0, 1.0),
+      (1.0, 2.0),
+      (0.0, 2.0),
+    ]);
+    let counter_clockwise = areas(&indexed(&points));
+    assert_eq!(counter_clockwise.len(), 4);
+    assert!(counter_clockwise.iter().all(|area| *area > 0.0));
+    assert_eq!(counter_clockwise.iter().sum::<f64>(), 6.0);
+
+    points.reverse();
+    let clockwise = areas(&indexed(&points));
+    assert!(clockwise.iter().all(|area| *area < 0.0));
+    assert_eq!(clockwise.iter().sum::<f64>(), -6.0);
+  }
+
+  #[test]
+  fn collinear_and_repeated_vertices_are_dropped() {
+    let points = outline(&[
+      (0.0, 0.0),
+      (1.0, 0.0),
+      (1.0, 0.0),
+      (2.0, 0.0),
+      (2.0, 2.0),
+      (0.0, 2.0),
+      (0.0, 0.0),
+    ]);
+    let areas = areas(&indexed(&points));
+    assert!(areas.iter().all(|area| *area > 0.0), "{:?}", areas);
+    assert_eq!(areas.iter().sum::<f64>(), 8.0);
+    assert!(triangulate(&outline(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)])).is_empty());
+  }
+
+  #[test]
+  fn bow_ties_are_split_at_their_crossing() {
+    let triangles = fill_triangles(&outline(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]));
+    assert_eq!(triangles.len(), 2);
+    assert!(triangles
+      .iter()
+      .all(|triangle| triangle.contains(&ScreenPoint::new(1.0, 1.0))));
+    assert_eq!(
+      areas(&triangles).iter().map(|area| area.abs()).sum::<f64>(),
+      4.0
+    );
+  }
+}
@@ -1,3 +1,11 @@
+#[cfg(feature = "nannou")]
 pub mod app;
+#[cfg(feature = "nannou")]
 pub mod draw;
 pub mod geometry;
+#[cfg(feature = "raster")]
+pub mod raster;
+
+/// Size of the pictures artworks are drawn to, by default, whether in a
+/// window or headlessly.
+pub const TEXTURE_SIZE: [u32; 2] = [2160, 2160];
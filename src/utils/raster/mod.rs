@@ -0,0 +1,444 @@
+//! Software counterpart of `utils::draw`, for rendering without a window or
+//! a GPU. Shapes are anti-aliased by exact area coverage and blended in
+//! linear RGB, as nannou does in its float texture.
+
+use super::geometry::{
+  brush_points, fill_triangles, split_self_intersections, triangulate, ScreenPoint,
+};
+use ab_glyph_rasterizer::{point, Rasterizer};
+use lyon_tessellation::{
+  geometry_builder::{BuffersBuilder, VertexBuffers},
+  math, path, StrokeOptions, StrokeTessellator, StrokeVertex,
+};
+use rand::rngs::StdRng;
+use std::path::Path;
+
+fn to_linear(channel: u8) -> f32 {
+  let channel = channel as f32 / 255.0;
+  if channel <= 0.04045 {
+    channel / 12.92
+  } else {
+    ((channel + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+fn to_srgb(channel: f32) -> u8 {
+  let channel = channel.clamp(0.0, 1.0);
+  let channel = if channel <= 0.0031308 {
+    channel * 12.92
+  } else {
+    1.055 * channel.powf(1.0 / 2.4) - 0.055
+  };
+  (channel * 255.0).round() as u8
+}
+
+fn linear(color: [u8; 3]) -> [f32; 3] {
+  color.map(to_linear)
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+  [0, 1, 2].map(|channel| a[channel] + (b[channel] - a[channel]) * t)
+}
+
+type Triangle = [(f32, f32); 3];
+
+/// Twice the signed area, positive when counter-clockwise in pixels.
+fn area(&[a, b, c]: &Triangle) -> f32 {
+  (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Part of `polygon` on the side of a line where `inside` holds, the line
+/// being crossed at `crossing` between two points.
+fn clip_side(
+  polygon: &[(f32, f32)],
+  inside: impl Fn((f32, f32)) -> bool,
+  crossing: impl Fn((f32, f32), (f32, f32)) -> (f32, f32),
+) -> Vec<(f32, f32)> {
+  let mut clipped = vec![];
+  for (index, point) in polygon.iter().enumerate() {
+    let previous = polygon[(index + polygon.len() - 1) % polygon.len()];
+    match (inside(previous), inside(*point)) {
+      (true, true) => clipped.push(*point),
+      (true, false) => clipped.push(crossing(previous, *point)),
+      (false, true) => {
+        clipped.push(crossing(previous, *point));
+        clipped.push(*point);
+      }
+      (false, false) => {}
+    }
+  }
+  clipped
+}
+
+/// Part of `triangle` within `0..width` and `0..height`, as the rasterizer
+/// writes out of its rows for points beyond them.
+fn clip(triangle: &Triangle, width: f32, height: f32) -> Vec<(f32, f32)> {
+  let at_x =
+    |x: f32| move |a: (f32, f32), b: (f32, f32)| (x, a.1 + (b.1 - a.1) * (x - a.0) / (b.0 - a.0));
+  let at_y =
+    |y: f32| move |a: (f32, f32), b: (f32, f32)| (a.0 + (b.0 - a.0) * (y - a.1) / (b.1 - a.1), y);
+  let polygon = clip_side(triangle, |(x, _)| x >= 0.0, at_x(0.0));
+  let polygon = clip_side(&polygon, |(x, _)| x <= width, at_x(width));
+  let polygon = clip_side(&polygon, |(_, y)| y >= 0.0, at_y(0.0));
+  clip_side(&polygon, |(_, y)| y <= height, at_y(height))
+}
+
+/// Coverage of a shape over the part of the canvas it overlaps.
+struct Coverage {
+  x: usize,
+  y: usize,
+  rasterizer: Rasterizer,
+}
+
+/// Image drawn in the coordinates of nannou's `Draw`: the origin at the
+/// center and y going up.
+pub struct Canvas {
+  width: usize,
+  height: usize,
+  pixels: Vec<[f32; 3]>,
+}
+
+impl Canvas {
+  /// Canvas of `size`, such as `utils::TEXTURE_SIZE`.
+  pub fn new(size: [u32; 2], background: [u8; 3]) -> Self {
+    let [width, height] = size.map(|side| side as usize);
+    Self {
+      width,
+      height,
+      pixels: vec![linear(background); width * height],
+    }
+  }
+
+  fn to_pixel(&self, point: ScreenPoint) -> (f32, f32) {
+    (
+      (point.x + self.width as f64 / 2.0) as f32,
+      (self.height as f64 / 2.0 - point.y) as f32,
+    )
+  }
+
+  /// Center of a pixel of `coverage`, back in drawing coordinates.
+  fn to_point(&self, coverage: &Coverage, x: u32, y: u32) -> ScreenPoint {
+    ScreenPoint::new(
+      (coverage.x + x as usize) as f64 + 0.5 - self.width as f64 / 2.0,
+      self.height as f64 / 2.0 - ((coverage.y + y as usize) as f64 + 0.5),
+    )
+  }
+
+  /// Union of `triangles`, whatever their winding.
+  fn cover(&self, triangles: &[Triangle]) -> Option<Coverage> {
+    let (mut min, mut max) = (
+      (f32::INFINITY, f32::INFINITY),
+      (f32::NEG_INFINITY, f32::NEG_INFINITY),
+    );
+    for (x, y) in triangles.iter().flatten() {
+      min = (min.0.min(*x), min.1.min(*y));
+      max = (max.0.max(*x), max.1.max(*y));
+    }
+    let x = min.0.floor().max(0.0) as usize;
+    let y = min.1.floor().max(0.0) as usize;
+    let width = (max.0.ceil().min(self.width as f32) as usize).checked_sub(x)?;
+    let height = (max.1.ceil().min(self.height as f32) as usize).checked_sub(y)?;
+    if width == 0 || height == 0 {
+      return None;
+    }
+    let mut rasterizer = Rasterizer::new(width, height);
+    let offset = |[a, b, c]: Triangle| [a, b, c].map(|(px, py)| (px - x as f32, py - y as f32));
+    for triangle in triangles {
+      let triangle = match area(triangle) {
+        area if area > 0.0 => offset(*triangle),
+        area if area < 0.0 => offset([triangle[0], triangle[2], triangle[1]]),
+        _ => continue,
+      };
+      let polygon = clip(&triangle, width as f32, height as f32);
+      for (index, (px, py)) in polygon.iter().enumerate() {
+        let (nx, ny) = polygon[(index + 1) % polygon.len()];
+        rasterizer.draw_line(point(*px, *py), point(nx, ny));
+      }
+    }
+    Some(Coverage { x, y, rasterizer })
+  }
+
+  /// Blends the color `shade` gives each covered pixel.
+  fn paint(&mut self, coverage: &Coverage, shade: impl Fn(u32, u32) -> [f32; 3]) {
+    coverage.rasterizer.for_each_pixel_2d(|x, y, alpha| {
+      let alpha = alpha.min(1.0);
+      if alpha > 0.0 {
+        let index = (coverage.y + y as usize) * self.width + coverage.x + x as usize;
+        self.pixels[index] = mix(self.pixels[index], shade(x, y), alpha);
+      }
+    });
+  }
+
+  fn fill_triangles(&mut self, triangles: &[Triangle], color: [u8; 3]) {
+    if let Some(coverage) = self.cover(triangles) {
+      let color = linear(color);
+      self.paint(&coverage, |_, _| color);
+    }
+  }
+
+  /// Polyline `stroke_weight` wide, with the joins and caps nannou uses.
+  pub fn stroke(&mut self, points: &[ScreenPoint], stroke_weight: f64, color: [u8; 3]) {
+    let points = points
+      .iter()
+      .map(|point| {
+        let (x, y) = self.to_pixel(*point);
+        math::point(x, y)
+      })
+      .collect::<Vec<_>>();
+    let mut buffers: VertexBuffers<math::Point, u32> = VertexBuffers::new();
+    let result = StrokeTessellator::new().tessellate_polygon(
+      path::Polygon {
+        points: &points,
+        closed: false,
+      },
+      &StrokeOptions::default().with_line_width(stroke_weight as f32),
+      &mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| vertex.position()),
+    );
+    if result.is_err() {
+      return;
+    }
+    let triangles = buffers
+      .indices
+      .chunks_exact(3)
+      .map(|triangle| {
+        [0, 1, 2].map(|corner| {
+          let vertex = buffers.vertices[triangle[corner] as usize];
+          (vertex.x, vertex.y)
+        })
+      })
+      .collect::<Vec<_>>();
+    self.fill_triangles(&triangles, color);
+  }
+
+  /// Same scattered polyline as `BrushDrawer::brush_from_points`, given the
+  /// same random generator.
+  pub fn brush(
+    &mut self,
+    points: &[ScreenPoint],
+    radius: f64,
+    rng: &mut StdRng,
+    stroke_weight: f64,
+    color: [u8; 3],
+  ) {
+    self.stroke(&brush_points(points, radius, rng), stroke_weight, color);
+  }
+
+  /// Filled outline, self-intersecting ones included.
+  pub fn fill(&mut self, points: &[ScreenPoint], color: [u8; 3]) {
+    let triangles = fill_triangles(points)
+      .into_iter()
+      .map(|triangle| triangle.map(|point| self.to_pixel(point)))
+      .collect::<Vec<_>>();
+    self.fill_triangles(&triangles, color);
+  }
+
+  /// Linear gradient from `start` to `end`, flat beyond them.
+  pub fn gradient(
+    &mut self,
+    points: &[ScreenPoint],
+    (start, start_color): (ScreenPoint, [u8; 3]),
+    (end, end_color): (ScreenPoint, [u8; 3]),
+  ) {
+    let triangles = fill_triangles(points)
+      .into_iter()
+      .map(|triangle| triangle.map(|point| self.to_pixel(point)))
+      .collect::<Vec<_>>();
+    let Some(coverage) = self.cover(&triangles) else {
+      return;
+    };
+    let (start_color, end_color) = (linear(start_color), linear(end_color));
+    let axis = end - start;
+    let length = axis.square_length();
+    let shades = (0..coverage.rasterizer.dimensions().1 as u32)
+      .flat_map(|y| (0..coverage.rasterizer.dimensions().0 as u32).map(move |x| (x, y)))
+      .map(|(x, y)| {
+        let t = if length > 0.0 {
+          ((self.to_point(&coverage, x, y) - start).dot(axis) / length).clamp(0.0, 1.0)
+        } else {
+          0.0
+        };
+        mix(start_color, end_color, t as f32)
+      })
+      .collect::<Vec<_>>();
+    let width = coverage.rasterizer.dimensions().0;
+    self.paint(&coverage, |x, y| shades[y as usize * width + x as usize]);
+  }
+
+  /// Colors interpolated between those of the vertices.
+  pub fn colored(&mut self, points: &[(ScreenPoint, [u8; 3])]) {
+    let points = points
+      .iter()
+      .map(|(point, color)| (self.to_pixel(*point), linear(*color)))
+      .collect::<Vec<_>>();
+    let position = |((x, y), _): &((f32, f32), [f32; 3])| ScreenPoint::new(*x as f64, *y as f64);
+    let triangles = split_self_intersections(&points, position, |(a, a_color), (b, b_color), t| {
+      let t = t as f32;
+      (
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t),
+        mix(*a_color, *b_color, t),
+      )
+    })
+    .iter()
+    .flat_map(|polygon| {
+      triangulate(&polygon.iter().map(position).collect::<Vec<_>>())
+        .into_iter()
+        .map(|triangle| triangle.map(|vertex| polygon[vertex]))
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+    let Some(coverage) = self.cover(
+      &triangles
+        .iter()
+        .map(|triangle| triangle.map(|(position, _)| position))
+        .collect::<Vec<_>>(),
+    ) else {
+      return;
+    };
+
+    // Each pixel takes its color from the triangle it lies deepest in, so
+    // pixels on the anti-aliased rim get one too.
+    let (width, height) = coverage.rasterizer.dimensions();
+    let mut shades = vec![(f32::NEG_INFINITY, [0.0; 3]); width * height];
+    for triangle in triangles.iter() {
+      let corners = triangle.map(|(position, _)| position);
+      let whole = area(&corners);
+      if whole == 0.0 {
+        continue;
+      }
+      let (x0, x1, y0, y1) = corners.iter().fold(
+        (
+          f32::INFINITY,
+          f32::NEG_INFINITY,
+          f32::INFINITY,
+          f32::NEG_INFINITY,
+        ),
+        |(x0, x1, y0, y1), (x, y)| (x0.min(*x), x1.max(*x), y0.min(*y), y1.max(*y)),
+      );
+      let columns = (x0 - 1.0).floor().max(coverage.x as f32) as usize
+        ..((x1 + 1.0).ceil() as usize).min(coverage.x + width);
+      let rows = (y0 - 1.0).floor().max(coverage.y as f32) as usize
+        ..((y1 + 1.0).ceil() as usize).min(coverage.y + height);
+      for row in rows {
+        for column in columns.clone() {
+          let center = (column as f32 + 0.5, row as f32 + 0.5);
+          // Barycentric weights, and the signed distance to each edge.
+          let mut weights = [0.0; 3];
+          let mut depth = f32::INFINITY;
+          for corner in 0..3 {
+            let (a, b) = (corners[(corner + 1) % 3], corners[(corner + 2) % 3]);
+            let edge = area(&[a, b, center]);
+            weights[corner] = (edge / whole).max(0.0);
+            let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            depth = depth.min(edge * whole.signum() / length);
+          }
+          let slot = &mut shades[(row - coverage.y) * width + column - coverage.x];
+          if depth > slot.0 {
+            let total = weights.iter().sum::<f32>();
+            let color = [0, 1, 2].map(|channel| {
+              (0..3)
+                .map(|corner| triangle[corner].1[channel] * weights[corner])
+                .sum::<f32>()
+                / total
+            });
+            *slot = (depth, color);
+          }
+        }
+      }
+    }
+    self.paint(&coverage, |x, y| shades[y as usize * width + x as usize].1);
+  }
+
+  /// Pixels as 8-bit sRGB, row after row from the top.
+  pub fn to_rgb8(&self) -> Vec<u8> {
+    self
+      .pixels
+      .iter()
+      .flat_map(|pixel| pixel.map(to_srgb))
+      .collect()
+  }
+
+  pub fn save_png(&self, path: impl AsRef<Path>) -> image::ImageResult<()> {
+    image::save_buffer_with_format(
+      path,
+      &self.to_rgb8(),
+      self.width as u32,
+      self.height as u32,
+      image::ColorType::Rgb8,
+      image::ImageFormat::Png,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SIZE: usize = 20;
+  const WHITE: [u8; 3] = [255, 255, 255];
+  const BLACK: [u8; 3] = [0, 0, 0];
+
+  /// Pixels painted over, as `(column, row)` from the top left.
+  fn painted(canvas: &Canvas) -> Vec<(usize, usize)> {
+    let background = linear(WHITE);
+    (0..canvas.height)
+      .flat_map(|row| (0..canvas.width).map(move |column| (column, row)))
+      .filter(|(column, row)| canvas.pixels[row * canvas.width + column] != background)
+      .collect()
+  }
+
+  fn pixels(columns: std::ops::Range<usize>, rows: std::ops::Range<usize>) -> Vec<(usize, usize)> {
+    rows
+      .flat_map(|row| columns.clone().map(move |column| (column, row)))
+      .collect()
+  }
+
+  /// Rectangle between two corners in pixels, in drawing coordinates.
+  fn rectangle(min: (f64, f64), max: (f64, f64)) -> Vec<ScreenPoint> {
+    let half = SIZE as f64 / 2.0;
+    [
+      (min.0, min.1),
+      (max.0, min.1),
+      (max.0, max.1),
+      (min.0, max.1),
+    ]
+    .iter()
+    .map(|(x, y)| ScreenPoint::new(x - half, half - y))
+    .collect()
+  }
+
+  fn fill(min: (f64, f64), max: (f64, f64)) -> Vec<(usize, usize)> {
+    let mut canvas = Canvas::new([SIZE as u32; 2], WHITE);
+    canvas.fill(&rectangle(min, max), BLACK);
+    painted(&canvas)
+  }
+
+  fn stroke(from: (f64, f64), to: (f64, f64)) -> Vec<(usize, usize)> {
+    let mut canvas = Canvas::new([SIZE as u32; 2], WHITE);
+    let half = SIZE as f64 / 2.0;
+    let points = [from, to].map(|(x, y)| ScreenPoint::new(x - half, half - y));
+    canvas.stroke(&points, 4.0, BLACK);
+    painted(&canvas)
+  }
+
+  #[test]
+  fn fills_crossing_each_edge() {
+    assert_eq!(fill((-30.0, 8.0), (5.0, 12.0)), pixels(0..5, 8..12));
+    assert_eq!(fill((15.0, 8.0), (50.0, 12.0)), pixels(15..20, 8..12));
+    assert_eq!(fill((8.0, -30.0), (12.0, 5.0)), pixels(8..12, 0..5));
+    assert_eq!(fill((8.0, 15.0), (12.0, 50.0)), pixels(8..12, 15..20));
+  }
+
+  #[test]
+  fn fills_beyond_the_canvas() {
+    assert_eq!(fill((-30.0, 8.0), (0.0, 12.0)), vec![]);
+    assert_eq!(fill((-30.0, -30.0), (50.0, 50.0)), pixels(0..20, 0..20));
+  }
+
+  #[test]
+  fn strokes_crossing_each_edge() {
+    assert_eq!(stroke((-30.0, 10.0), (10.0, 10.0)), pixels(0..10, 8..12));
+    assert_eq!(stroke((10.0, 10.0), (50.0, 10.0)), pixels(10..20, 8..12));
+    assert_eq!(stroke((10.0, -30.0), (10.0, 10.0)), pixels(8..12, 0..10));
+    assert_eq!(stroke((10.0, 10.0), (10.0, 50.0)), pixels(8..12, 10..20));
+  }
+}